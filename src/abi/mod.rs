@@ -0,0 +1,182 @@
+use alloy::dyn_abi::{ DynSolType, DynSolValue, FunctionExt, JsonAbiExt, Specifier };
+use alloy::json_abi::{ Function, JsonAbi };
+use alloy::primitives::{ Address, Bytes, U256 };
+
+use std::path::Path;
+
+use crate::simulator::{ SimOutcome, SimStatus, Simulator };
+
+
+/// A contract function that is encoded and decoded at runtime
+///
+/// Useful to call any contract without writing `sol!` bindings for it
+#[derive(Debug, Clone)]
+pub struct DynCall {
+    pub function: Function,
+}
+
+impl DynCall {
+    /// Parses a function from a signature
+    ///
+    /// Both `getReserves()(uint112,uint112,uint32)` and
+    /// `function getReserves() returns (uint112,uint112,uint32)` are accepted
+    pub fn from_signature(signature: &str) -> Result<Self, anyhow::Error> {
+        let function = Function::parse(signature).map_err(|e| {
+            anyhow::anyhow!("Invalid function signature {}: {}", signature, e)
+        })?;
+        Ok(Self { function })
+    }
+
+    /// Finds a function in an ABI by its name or by its signature, eg. `balanceOf(address)`
+    ///
+    /// Use the signature for overloaded functions, otherwise the first match is returned
+    pub fn from_abi(abi: &JsonAbi, name: &str) -> Result<Self, anyhow::Error> {
+        let function = if name.contains('(') {
+            abi.functions().find(|f| f.signature() == name)
+        } else {
+            abi.function(name).and_then(|f| f.first())
+        };
+
+        match function {
+            Some(function) => Ok(Self { function: function.clone() }),
+            None => Err(anyhow::anyhow!("Function {} not found in ABI", name)),
+        }
+    }
+
+    /// Encodes the calldata, selector included
+    pub fn encode(&self, args: &[DynSolValue]) -> Result<Bytes, anyhow::Error> {
+        Ok(self.function.abi_encode_input(args)?.into())
+    }
+
+    /// Encodes the calldata from string arguments, eg. `["0xC02a...", "1000000"]`
+    ///
+    /// Each argument is parsed according to the function's input types
+    pub fn encode_str(&self, args: &[&str]) -> Result<Bytes, anyhow::Error> {
+        let values = parse_args(&self.function, args)?;
+        self.encode(&values)
+    }
+
+    /// Decodes the return data of the function
+    pub fn decode_output(&self, data: &[u8]) -> Result<Vec<DynSolValue>, anyhow::Error> {
+        Ok(self.function.abi_decode_output(data, true)?)
+    }
+
+    /// Calls the function on the fork without committing the state changes and decodes the output
+    pub fn call(
+        &self,
+        simulator: &mut Simulator,
+        caller: Address,
+        to: Address,
+        args: &[DynSolValue]
+    ) -> Result<Vec<DynSolValue>, anyhow::Error> {
+        let data = self.encode(args)?;
+        let outcome = simulator.call(caller, to, data, U256::ZERO)?;
+        self.decode_outcome(outcome)
+    }
+
+    /// Sends the function as a transaction on the fork, commits the state changes and decodes the output
+    pub fn transact(
+        &self,
+        simulator: &mut Simulator,
+        caller: Address,
+        to: Address,
+        args: &[DynSolValue],
+        value: U256
+    ) -> Result<Vec<DynSolValue>, anyhow::Error> {
+        let data = self.encode(args)?;
+        let outcome = simulator.transact(caller, to, data, value)?;
+        self.decode_outcome(outcome)
+    }
+
+    fn decode_outcome(&self, outcome: SimOutcome) -> Result<Vec<DynSolValue>, anyhow::Error> {
+        match outcome.status {
+            SimStatus::Success => self.decode_output(&outcome.output),
+            SimStatus::Revert(reason) => {
+                Err(anyhow::anyhow!("{} reverted, Reason: {}", self.function.name, reason))
+            }
+            SimStatus::Halt(reason) => {
+                Err(anyhow::anyhow!("{} halted, Reason: {:?}", self.function.name, reason))
+            }
+        }
+    }
+}
+
+/// A contract with an ABI loaded at runtime
+#[derive(Debug, Clone)]
+pub struct DynContract {
+    pub address: Address,
+    pub abi: JsonAbi,
+}
+
+impl DynContract {
+    pub fn new(address: Address, abi: JsonAbi) -> Self {
+        Self { address, abi }
+    }
+
+    /// Loads the ABI from a json file
+    ///
+    /// Accepts a plain ABI array or an artifact with an `abi` field
+    pub fn from_abi_file(address: Address, path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let json = std::fs::read_to_string(path.as_ref())?;
+        Ok(Self::new(address, parse_abi(&json)?))
+    }
+
+    /// Returns the function by its name or signature
+    pub fn function(&self, name: &str) -> Result<DynCall, anyhow::Error> {
+        DynCall::from_abi(&self.abi, name)
+    }
+
+    /// Calls a function without committing the state changes and decodes the output
+    pub fn call(
+        &self,
+        simulator: &mut Simulator,
+        caller: Address,
+        name: &str,
+        args: &[DynSolValue]
+    ) -> Result<Vec<DynSolValue>, anyhow::Error> {
+        self.function(name)?.call(simulator, caller, self.address, args)
+    }
+
+    /// Sends a function as a transaction, commits the state changes and decodes the output
+    pub fn transact(
+        &self,
+        simulator: &mut Simulator,
+        caller: Address,
+        name: &str,
+        args: &[DynSolValue],
+        value: U256
+    ) -> Result<Vec<DynSolValue>, anyhow::Error> {
+        self.function(name)?.transact(simulator, caller, self.address, args, value)
+    }
+}
+
+/// Parses an ABI from json, either a plain ABI array or an artifact with an `abi` field
+pub fn parse_abi(json: &str) -> Result<JsonAbi, anyhow::Error> {
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let abi = match value.get("abi") {
+        Some(abi) => abi.to_string(),
+        None => json.to_string(),
+    };
+    Ok(serde_json::from_str(&abi)?)
+}
+
+// Parses string arguments according to the input types of the function
+fn parse_args(function: &Function, args: &[&str]) -> Result<Vec<DynSolValue>, anyhow::Error> {
+    if function.inputs.len() != args.len() {
+        return Err(
+            anyhow::anyhow!(
+                "{} expects {} arguments, got {}",
+                function.name,
+                function.inputs.len(),
+                args.len()
+            )
+        );
+    }
+
+    let mut values = Vec::new();
+    for (param, arg) in function.inputs.iter().zip(args) {
+        let ty: DynSolType = param.resolve()?;
+        values.push(ty.coerce_str(arg)?);
+    }
+    Ok(values)
+}
@@ -0,0 +1,360 @@
+use alloy::primitives::{ keccak256, Address, Bytes, B256, U256 };
+use revm::primitives::TxEnv;
+use revm::Database;
+use serde::{ Serialize, Serializer };
+
+use crate::simulator::{ SimOutcome, SimStatus, Simulator };
+use crate::tx_env::decode_raw_tx;
+
+
+/// A transaction of a [Bundle]
+#[derive(Debug, Clone)]
+pub struct BundleTx {
+    pub tx: TxEnv,
+
+    /// Hash of the signed transaction, `None` for unsigned transactions
+    pub hash: Option<B256>,
+
+    /// If false a revert of this transaction makes the whole bundle invalid
+    pub allowed_to_revert: bool,
+}
+
+/// An ordered list of transactions executed one after the other on the same state, like a Flashbots bundle
+#[derive(Debug, Clone, Default)]
+pub struct Bundle {
+    pub txs: Vec<BundleTx>,
+}
+
+impl Bundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a signed raw transaction
+    pub fn push_raw(&mut self, raw: &[u8], allowed_to_revert: bool) -> Result<(), anyhow::Error> {
+        let decoded = decode_raw_tx(raw)?;
+        self.txs.push(BundleTx {
+            tx: decoded.tx_env,
+            hash: Some(decoded.hash),
+            allowed_to_revert,
+        });
+        Ok(())
+    }
+
+    /// Adds an unsigned transaction, useful to simulate a bundle before signing it
+    pub fn push_tx(&mut self, tx: TxEnv, allowed_to_revert: bool) {
+        self.txs.push(BundleTx {
+            tx,
+            hash: None,
+            allowed_to_revert,
+        });
+    }
+
+    /// `keccak256` of the concatenated transaction hashes, the same as `bundleHash` of `eth_callBundle`
+    pub fn hash(&self) -> B256 {
+        let hashes: Vec<u8> = self.txs
+            .iter()
+            .filter_map(|tx| tx.hash)
+            .flat_map(|hash| hash.0)
+            .collect();
+        keccak256(hashes)
+    }
+
+    /// Executes the transactions in order and commits the state changes of a valid bundle
+    ///
+    /// Like `eth_callBundle`, reverted transactions don't stop the execution,
+    /// [BundleResult::is_valid] checks the `allowed_to_revert` flags.
+    /// Returns an error if a transaction is invalid, eg. a wrong nonce.
+    /// The state is left as it was when the bundle is invalid or fails
+    ///
+    /// The fees are paid with the base fee of the block header, also in [crate::EvmMode::Relaxed]
+    pub fn simulate(&self, simulator: &mut Simulator) -> Result<BundleResult, anyhow::Error> {
+        let snapshot = simulator.evm.db().clone();
+        let result = simulator.with_header_basefee(|simulator| self.execute(simulator));
+        if !result.as_ref().is_ok_and(BundleResult::is_valid) {
+            *simulator.evm.db_mut() = snapshot;
+        }
+        result
+    }
+
+    fn execute(&self, simulator: &mut Simulator) -> Result<BundleResult, anyhow::Error> {
+        let coinbase = simulator.evm.block().coinbase;
+        let basefee = simulator.evm.block().basefee;
+
+        let mut results = Vec::new();
+        for bundle_tx in &self.txs {
+            let balance_before = balance(simulator, coinbase)?;
+
+            *simulator.evm.tx_mut() = bundle_tx.tx.clone();
+            let tip = simulator.evm.context.evm.env.effective_gas_price().saturating_sub(basefee);
+
+            let outcome = simulator.transact_env(bundle_tx.tx.clone())?;
+            let coinbase_diff = balance(simulator, coinbase)?.saturating_sub(balance_before);
+
+            results.push(BundleTxResult::new(bundle_tx, outcome, coinbase_diff, tip));
+        }
+
+        let total_gas_used = results.iter().map(|r| r.gas_used).sum();
+        let coinbase_diff = results.iter().map(|r| r.coinbase_diff).sum();
+        let gas_fees = results.iter().map(|r| r.gas_fees).sum();
+        let eth_sent_to_coinbase = results.iter().map(|r| r.eth_sent_to_coinbase).sum();
+
+        Ok(BundleResult {
+            bundle_gas_price: price_per_gas(coinbase_diff, total_gas_used),
+            bundle_hash: self.hash(),
+            coinbase_diff,
+            eth_sent_to_coinbase,
+            gas_fees,
+            results,
+            state_block_number: simulator.evm.block().number.to::<u64>(),
+            total_gas_used,
+        })
+    }
+}
+
+/// The result of a simulated [Bundle], serializes to the same shape as Flashbots `eth_callBundle`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleResult {
+    /// Coinbase payment per unit of gas, the effective gas price of the whole bundle
+    #[serde(serialize_with = "as_decimal")]
+    pub bundle_gas_price: U256,
+
+    pub bundle_hash: B256,
+
+    /// Total coinbase balance change, the profit of the builder
+    #[serde(serialize_with = "as_decimal")]
+    pub coinbase_diff: U256,
+
+    /// Direct transfers to the coinbase
+    #[serde(serialize_with = "as_decimal")]
+    pub eth_sent_to_coinbase: U256,
+
+    /// Priority fees paid to the coinbase
+    #[serde(serialize_with = "as_decimal")]
+    pub gas_fees: U256,
+
+    pub results: Vec<BundleTxResult>,
+
+    pub state_block_number: u64,
+
+    pub total_gas_used: u64,
+}
+
+impl BundleResult {
+    /// Coinbase balance change of the bundle, priority fees and direct transfers
+    pub fn builder_profit(&self) -> U256 {
+        self.coinbase_diff
+    }
+
+    /// False if a transaction that is not allowed to revert reverted or halted
+    pub fn is_valid(&self) -> bool {
+        self.results.iter().all(|r| r.allowed_to_revert || r.outcome.is_success())
+    }
+
+    /// Serializes the result like the response of `eth_callBundle`
+    pub fn to_json(&self) -> Result<serde_json::Value, anyhow::Error> {
+        Ok(serde_json::to_value(self)?)
+    }
+}
+
+/// The result of a transaction of a [Bundle]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleTxResult {
+    #[serde(serialize_with = "as_decimal")]
+    pub coinbase_diff: U256,
+
+    #[serde(serialize_with = "as_decimal")]
+    pub eth_sent_to_coinbase: U256,
+
+    pub from_address: Address,
+
+    #[serde(serialize_with = "as_decimal")]
+    pub gas_fees: U256,
+
+    /// Coinbase payment per unit of gas
+    #[serde(serialize_with = "as_decimal")]
+    pub gas_price: U256,
+
+    pub gas_used: u64,
+
+    /// `None` for contract creations
+    pub to_address: Option<Address>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<B256>,
+
+    /// Return data of a successful transaction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Bytes>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Decoded revert message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert: Option<String>,
+
+    #[serde(skip)]
+    pub allowed_to_revert: bool,
+
+    #[serde(skip)]
+    pub outcome: SimOutcome,
+}
+
+impl BundleTxResult {
+    fn new(bundle_tx: &BundleTx, outcome: SimOutcome, coinbase_diff: U256, tip: U256) -> Self {
+        let gas_fees = U256::from(outcome.gas_used) * tip;
+
+        let (value, error, revert) = match &outcome.status {
+            SimStatus::Success => (Some(outcome.output.clone()), None, None),
+            SimStatus::Revert(reason) => {
+                let revert = (!outcome.output.is_empty()).then(|| reason.clone());
+                (None, Some("execution reverted".to_string()), revert)
+            }
+            SimStatus::Halt(reason) => (None, Some(format!("{:?}", reason)), None),
+        };
+
+        Self {
+            coinbase_diff,
+            eth_sent_to_coinbase: coinbase_diff.saturating_sub(gas_fees),
+            from_address: bundle_tx.tx.caller,
+            gas_fees,
+            gas_price: price_per_gas(coinbase_diff, outcome.gas_used),
+            gas_used: outcome.gas_used,
+            to_address: bundle_tx.tx.transact_to.to().copied(),
+            tx_hash: bundle_tx.hash,
+            value,
+            error,
+            revert,
+            allowed_to_revert: bundle_tx.allowed_to_revert,
+            outcome,
+        }
+    }
+}
+
+fn balance(simulator: &mut Simulator, address: Address) -> Result<U256, anyhow::Error> {
+    let info = simulator.evm.db_mut().basic(address)?;
+    Ok(info.map(|i| i.balance).unwrap_or_default())
+}
+
+fn price_per_gas(amount: U256, gas_used: u64) -> U256 {
+    if gas_used == 0 {
+        U256::ZERO
+    } else {
+        amount / U256::from(gas_used)
+    }
+}
+
+// `eth_callBundle` returns wei amounts as decimal strings
+fn as_decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvmMode;
+    use alloy::primitives::{ address, bytes };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode, TransactTo };
+    use serde_json::json;
+
+    const CALLER: Address = address!("1111111111111111111111111111111111111111");
+    const REVERTER: Address = address!("2222222222222222222222222222222222222222");
+    const GWEI: u64 = 1_000_000_000;
+
+    fn simulator() -> Simulator {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CALLER, AccountInfo {
+            balance: U256::from(10).pow(U256::from(18)),
+            ..Default::default()
+        });
+        // REVERT(0, 0)
+        db.insert_account_info(REVERTER, AccountInfo {
+            code: Some(Bytecode::new_raw(bytes!("60006000fd"))),
+            ..Default::default()
+        });
+        // the coinbase of the offline block is the zero address
+        Simulator::offline(db, EvmMode::Relaxed)
+    }
+
+    fn tx(to: Address, value: u64, nonce: u64) -> TxEnv {
+        TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(to),
+            value: U256::from(value),
+            gas_limit: 100_000,
+            gas_price: U256::from(2 * GWEI),
+            nonce: Some(nonce),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn invalid_bundle_is_not_committed() {
+        let mut simulator = simulator();
+        let mut bundle = Bundle::new();
+        bundle.push_tx(tx(Address::ZERO, 1000, 0), false);
+        bundle.push_tx(tx(REVERTER, 0, 1), false);
+
+        let result = bundle.simulate(&mut simulator).unwrap();
+        assert!(!result.is_valid());
+        assert_eq!(simulator.nonce(CALLER).unwrap(), 0);
+
+        // the same bundle is valid when the revert is allowed
+        bundle.txs[1].allowed_to_revert = true;
+        let result = bundle.simulate(&mut simulator).unwrap();
+        assert!(result.is_valid());
+        assert_eq!(simulator.nonce(CALLER).unwrap(), 2);
+    }
+
+    #[test]
+    fn call_bundle_json() {
+        let mut simulator = simulator();
+        let mut bundle = Bundle::new();
+        bundle.push_tx(tx(Address::ZERO, 1000, 0), false);
+        bundle.push_tx(tx(REVERTER, 0, 1), true);
+
+        let result = bundle.simulate(&mut simulator).unwrap();
+        let json = result.to_json().unwrap();
+
+        // the tip is 1 gwei above the base fee
+        let transfer_fees = 21_000 * GWEI;
+        let revert_fees = result.results[1].gas_used * GWEI;
+        let total_gas_used = 21_000 + result.results[1].gas_used;
+        let coinbase_diff = transfer_fees + revert_fees + 1000;
+        assert_eq!(json, json!({
+            "bundleGasPrice": (coinbase_diff / total_gas_used).to_string(),
+            "bundleHash": keccak256([]),
+            "coinbaseDiff": coinbase_diff.to_string(),
+            "ethSentToCoinbase": "1000",
+            "gasFees": (transfer_fees + revert_fees).to_string(),
+            "results": [
+                {
+                    "coinbaseDiff": (transfer_fees + 1000).to_string(),
+                    "ethSentToCoinbase": "1000",
+                    "fromAddress": CALLER,
+                    "gasFees": transfer_fees.to_string(),
+                    "gasPrice": ((transfer_fees + 1000) / 21_000).to_string(),
+                    "gasUsed": 21_000,
+                    "toAddress": Address::ZERO,
+                    "value": "0x",
+                },
+                {
+                    "coinbaseDiff": revert_fees.to_string(),
+                    "ethSentToCoinbase": "0",
+                    "fromAddress": CALLER,
+                    "gasFees": revert_fees.to_string(),
+                    "gasPrice": GWEI.to_string(),
+                    "gasUsed": result.results[1].gas_used,
+                    "toAddress": REVERTER,
+                    "error": "execution reverted",
+                },
+            ],
+            "stateBlockNumber": 1,
+            "totalGasUsed": total_gas_used,
+        }));
+    }
+}
@@ -0,0 +1,23 @@
+use alloy::primitives::{ Address, U256 };
+use revm::Database;
+
+use crate::forked_db::fork_db::ForkDB;
+use super::find_allowance_slot;
+
+
+/// Computes the storage write `(address, slot, value)` that sets `allowance[owner][spender]` to `amount`
+pub fn allowance_storage(
+    fork_db: &ForkDB,
+    token: Address,
+    owner: Address,
+    spender: Address,
+    amount: U256
+) -> Result<(Address, U256, U256), anyhow::Error> {
+    let mut db = fork_db.clone();
+
+    let allowance_slot = find_allowance_slot(&db, token, owner, spender)?;
+    let slot = allowance_slot.slot(&[owner, spender]);
+    let word = db.storage(allowance_slot.storage_address, slot)?;
+
+    Ok((allowance_slot.storage_address, slot, allowance_slot.pack(word, amount)?))
+}
@@ -0,0 +1,51 @@
+use alloy::primitives::{ Address, U256 };
+use revm::Database;
+
+use crate::forked_db::fork_db::ForkDB;
+use super::{ find_balance_slot, find_total_supply_slot, total_supply_is_stored };
+
+
+/// Computes the storage writes `(address, slot, value)` that set the ERC20 balance of `account` to `amount`
+///
+/// If `adjust_total_supply` is true `totalSupply` is changed by the difference of the old and new balance.
+/// Tokens whose supply is not in storage are left as is, eg. WETH9 returns its ETH balance as the supply
+pub fn deal_storage(
+    fork_db: &ForkDB,
+    token: Address,
+    account: Address,
+    amount: U256,
+    adjust_total_supply: bool
+) -> Result<Vec<(Address, U256, U256)>, anyhow::Error> {
+    let mut db = fork_db.clone();
+
+    let balance_slot = find_balance_slot(&db, token, account)?;
+    let slot = balance_slot.slot(&[account]);
+    let word = db.storage(balance_slot.storage_address, slot)?;
+    let old_balance = balance_slot.unpack(word);
+
+    let mut writes = vec![(balance_slot.storage_address, slot, balance_slot.pack(word, amount)?)];
+
+    if adjust_total_supply && total_supply_is_stored(&db, token)? {
+        let supply_slot = find_total_supply_slot(&db, token)?;
+        let address = supply_slot.storage_address;
+        let slot = supply_slot.slot(&[]);
+
+        // the supply can be packed in the same slot as the balance
+        let word = match writes.iter().find(|(a, s, _)| *a == address && *s == slot) {
+            Some((_, _, value)) => *value,
+            None => db.storage(address, slot)?,
+        };
+
+        let supply = supply_slot.unpack(word);
+        let new_supply = if amount >= old_balance {
+            supply.saturating_add(amount - old_balance)
+        } else {
+            supply.saturating_sub(old_balance - amount)
+        };
+
+        writes.retain(|(a, s, _)| !(*a == address && *s == slot));
+        writes.push((address, slot, supply_slot.pack(word, new_supply)?));
+    }
+
+    Ok(writes)
+}
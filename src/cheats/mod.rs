@@ -0,0 +1,8 @@
+pub mod storage_slot;
+pub use storage_slot::*;
+
+pub mod deal;
+pub use deal::*;
+
+pub mod allowance;
+pub use allowance::*;
@@ -0,0 +1,387 @@
+use alloy::primitives::{ keccak256, Address, Bytes, B256, U256 };
+use alloy::sol_types::SolCall;
+use hashbrown::{ HashMap, HashSet };
+use lazy_static::lazy_static;
+use revm::{
+    inspector_handle_register,
+    primitives::{ ExecutionResult, Output, TransactTo },
+    Database,
+    Evm,
+};
+
+use std::sync::Mutex;
+
+use crate::forked_db::fork_db::ForkDB;
+use crate::inspectors::StorageAccessInspector;
+use crate::utils::ERC20;
+
+
+// the same address can hold another contract on another chain or block, so the slots are cached per fork
+type SlotCache = Mutex<HashMap<(u64, Address), MappingSlot>>;
+
+lazy_static! {
+    // (fork id, token) -> balance mapping slot
+    static ref BALANCE_SLOTS: SlotCache = Mutex::new(HashMap::new());
+
+    // (fork id, token) -> allowance mapping slot
+    static ref ALLOWANCE_SLOTS: SlotCache = Mutex::new(HashMap::new());
+
+    // (fork id, token) -> total supply slot
+    static ref TOTAL_SUPPLY_SLOTS: SlotCache = Mutex::new(HashMap::new());
+}
+
+/// Order of the words hashed to get the slot of a mapping key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingLayout {
+    /// `keccak256(key ++ slot)`
+    Solidity,
+
+    /// `keccak256(slot ++ key)`
+    Vyper,
+}
+
+/// Location of a mapping in the storage of a contract, eg. `balanceOf` of an ERC20 token
+///
+/// A plain variable like `totalSupply` is a mapping without keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappingSlot {
+    /// Contract that holds the storage, for proxies this is the proxy itself
+    pub storage_address: Address,
+
+    /// The slot the mapping is declared at
+    pub base_slot: U256,
+
+    pub layout: MappingLayout,
+
+    /// Bit offset of the value in the slot, non zero if the value is packed with other variables
+    pub offset: usize,
+
+    /// Width of the value in bits
+    pub bits: usize,
+}
+
+impl MappingSlot {
+    /// Computes the slot of `mapping[keys[0]][keys[1]]..`
+    pub fn slot(&self, keys: &[Address]) -> U256 {
+        let mut slot = self.base_slot;
+        for key in keys {
+            let key = B256::left_padding_from(key.as_slice());
+            let slot_bytes = B256::from(slot);
+
+            let data = match self.layout {
+                MappingLayout::Solidity => [key, slot_bytes].concat(),
+                MappingLayout::Vyper => [slot_bytes, key].concat(),
+            };
+            slot = keccak256(&data).into();
+        }
+        slot
+    }
+
+    /// Writes `value` into the `current` word of the slot, keeping the bits of any packed variables
+    ///
+    /// Fails if `value` doesn't fit in the width of the slot instead of truncating it
+    pub fn pack(&self, current: U256, value: U256) -> Result<U256, anyhow::Error> {
+        if value > value_mask(self.bits) {
+            return Err(anyhow::anyhow!("{} does not fit in the {} bits of the slot", value, self.bits));
+        }
+        Ok(replace_bits(current, value, self.offset, self.bits))
+    }
+
+    /// Reads the value from the word of the slot
+    pub fn unpack(&self, word: U256) -> U256 {
+        (word >> self.offset) & value_mask(self.bits)
+    }
+}
+
+/// Finds the slot of the `balanceOf` mapping of an ERC20 token
+///
+/// Runs `balanceOf(account)` recording every `SLOAD`, then overwrites the candidate slots
+/// until `balanceOf` returns the written value. Works with Solidity and Vyper tokens,
+/// proxies and balances packed with other variables
+///
+/// Results are cached per fork and token, see [ForkDB::fork_id]
+pub fn find_balance_slot(
+    fork_db: &ForkDB,
+    token: Address,
+    account: Address
+) -> Result<MappingSlot, anyhow::Error> {
+    if let Some(slot) = BALANCE_SLOTS.lock().unwrap().get(&(fork_db.fork_id(), token)) {
+        return Ok(*slot);
+    }
+
+    let data: Bytes = (ERC20::balanceOfCall { owner: account }).abi_encode().into();
+    let slot = find_mapping_slot(fork_db, token, data, &[account])?;
+
+    BALANCE_SLOTS.lock().unwrap().insert((fork_db.fork_id(), token), slot);
+    Ok(slot)
+}
+
+/// Finds the slot of the nested `allowance[owner][spender]` mapping of an ERC20 token
+///
+/// Discovered the same way as [find_balance_slot] and verified with `allowance(owner, spender)`
+///
+/// Results are cached per fork and token, see [ForkDB::fork_id]
+pub fn find_allowance_slot(
+    fork_db: &ForkDB,
+    token: Address,
+    owner: Address,
+    spender: Address
+) -> Result<MappingSlot, anyhow::Error> {
+    if let Some(slot) = ALLOWANCE_SLOTS.lock().unwrap().get(&(fork_db.fork_id(), token)) {
+        return Ok(*slot);
+    }
+
+    let data: Bytes = (ERC20::allowanceCall { owner, spender }).abi_encode().into();
+    let slot = find_mapping_slot(fork_db, token, data, &[owner, spender])?;
+
+    ALLOWANCE_SLOTS.lock().unwrap().insert((fork_db.fork_id(), token), slot);
+    Ok(slot)
+}
+
+/// Finds the slot of the `totalSupply` variable of an ERC20 token
+///
+/// Results are cached per fork and token, see [ForkDB::fork_id]
+pub fn find_total_supply_slot(fork_db: &ForkDB, token: Address) -> Result<MappingSlot, anyhow::Error> {
+    if let Some(slot) = TOTAL_SUPPLY_SLOTS.lock().unwrap().get(&(fork_db.fork_id(), token)) {
+        return Ok(*slot);
+    }
+
+    let data: Bytes = (ERC20::totalSupplyCall {}).abi_encode().into();
+    let slot = find_mapping_slot(fork_db, token, data, &[])?;
+
+    TOTAL_SUPPLY_SLOTS.lock().unwrap().insert((fork_db.fork_id(), token), slot);
+    Ok(slot)
+}
+
+// False for tokens like WETH9 whose `totalSupply()` returns their ETH balance instead of reading storage
+pub(crate) fn total_supply_is_stored(fork_db: &ForkDB, token: Address) -> Result<bool, anyhow::Error> {
+    if TOTAL_SUPPLY_SLOTS.lock().unwrap().contains_key(&(fork_db.fork_id(), token)) {
+        return Ok(true);
+    }
+
+    let data: Bytes = (ERC20::totalSupplyCall {}).abi_encode().into();
+    let (_, inspector, _) = view_call(fork_db.clone(), token, data)?;
+    Ok(!inspector.sloads.is_empty())
+}
+
+// Generic discovery of `mapping[keys[0]][keys[1]]..` read by the call, the call must return a single uint
+// With no keys any slot read by the call is a candidate
+pub(crate) fn find_mapping_slot(
+    fork_db: &ForkDB,
+    contract: Address,
+    data: Bytes,
+    keys: &[Address]
+) -> Result<MappingSlot, anyhow::Error> {
+    let (_, inspector, mut db) = view_call(fork_db.clone(), contract, data.clone())?;
+
+    // the value is usually read last, so start from the end, a slot read several times is tried once
+    let mut seen = HashSet::new();
+    let candidates: Vec<_> = inspector.sloads
+        .iter()
+        .rev()
+        .filter(|sload| seen.insert(**sload))
+        .copied()
+        .collect();
+
+    for (storage_address, slot) in candidates {
+        let (layout, base_slot) = if keys.is_empty() {
+            (MappingLayout::Solidity, slot)
+        } else {
+            match resolve_mapping(slot, keys, &inspector.preimages) {
+                Some(mapping) => mapping,
+                None => {
+                    continue;
+                }
+            }
+        };
+
+        let original = db.storage(storage_address, slot)?;
+        let (offset, bits) = match probe_slot(&db, contract, &data, storage_address, slot, original)? {
+            Some(found) => found,
+            None => {
+                continue;
+            }
+        };
+
+        return Ok(MappingSlot {
+            storage_address,
+            base_slot,
+            layout,
+            offset,
+            bits,
+        });
+    }
+
+    Err(anyhow::anyhow!("Could not find the storage slot for {:?}", contract))
+}
+
+// Writes a probe value at every byte offset of the slot until the call returns it
+// then finds the width of the value, returns `(offset, bits)`
+fn probe_slot(
+    db: &ForkDB,
+    contract: Address,
+    data: &Bytes,
+    storage_address: Address,
+    slot: U256,
+    original: U256
+) -> Result<Option<(usize, usize)>, anyhow::Error> {
+    let probe = U256::from(0x1337_c0de_u64);
+
+    for offset in (0..256).step_by(8) {
+        let word = replace_bits(original, probe, offset, 256 - offset);
+        if read_with_slot(db, contract, data, storage_address, slot, word)? != Some(probe) {
+            continue;
+        }
+
+        // Largest width that survives the round trip
+        let max_bits = 256 - offset;
+        for bits in [max_bits, 128, 112, 96, 80, 64, 48, 32] {
+            if bits > max_bits {
+                continue;
+            }
+            let value = value_mask(bits);
+            let word = replace_bits(original, value, offset, bits);
+            if read_with_slot(db, contract, data, storage_address, slot, word)? == Some(value) {
+                return Ok(Some((offset, bits)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// Returns the value of the call after overriding the slot
+pub(crate) fn read_with_slot(
+    db: &ForkDB,
+    contract: Address,
+    data: &Bytes,
+    storage_address: Address,
+    slot: U256,
+    word: U256
+) -> Result<Option<U256>, anyhow::Error> {
+    let mut db = db.clone();
+    db.insert_account_storage(storage_address, slot, word)?;
+
+    let (output, _, _) = view_call(db, contract, data.clone())?;
+    Ok(output.filter(|o| o.len() == 32).map(|o| U256::from_be_slice(&o)))
+}
+
+// Follows the hash preimages from the slot back to the mapping declaration
+fn resolve_mapping(
+    slot: U256,
+    keys: &[Address],
+    preimages: &HashMap<B256, (U256, U256)>
+) -> Option<(MappingLayout, U256)> {
+    let (key, parent_keys) = keys.split_last()?;
+    let key = U256::from_be_slice(key.as_slice());
+    let (first, second) = preimages.get(&B256::from(slot))?;
+
+    let (layout, parent) = if *first == key {
+        (MappingLayout::Solidity, *second)
+    } else if *second == key {
+        (MappingLayout::Vyper, *first)
+    } else {
+        return None;
+    };
+
+    if parent_keys.is_empty() {
+        return Some((layout, parent));
+    }
+
+    match resolve_mapping(parent, parent_keys, preimages)? {
+        (parent_layout, base) if parent_layout == layout => Some((layout, base)),
+        _ => None,
+    }
+}
+
+// Executes a call in the block of the fork recording the storage access, returns the output if the call succeeded
+pub(crate) fn view_call(
+    db: ForkDB,
+    to: Address,
+    data: Bytes
+) -> Result<(Option<Bytes>, StorageAccessInspector, ForkDB), anyhow::Error> {
+    let block_env = db.block_env().clone();
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .with_external_context(StorageAccessInspector::default())
+        .modify_block_env(|block| *block = block_env)
+        .modify_cfg_env(|cfg| {
+            // the call has no gas price and the default gas limit
+            cfg.disable_base_fee = true;
+            cfg.disable_block_gas_limit = true;
+        })
+        .modify_tx_env(|tx| {
+            tx.caller = Address::ZERO;
+            tx.transact_to = TransactTo::Call(to);
+            tx.data = data;
+        })
+        .append_handler_register(inspector_handle_register)
+        .build();
+    let res = evm.transact()?;
+
+    let output = match res.result {
+        ExecutionResult::Success { output: Output::Call(bytes), .. } => Some(bytes),
+        _ => None,
+    };
+
+    let context = evm.into_context();
+    Ok((output, context.external, context.evm.inner.db))
+}
+
+fn value_mask(bits: usize) -> U256 {
+    if bits >= 256 {
+        U256::MAX
+    } else {
+        (U256::from(1) << bits) - U256::from(1)
+    }
+}
+
+fn replace_bits(word: U256, value: U256, offset: usize, bits: usize) -> U256 {
+    let mask = value_mask(bits) << offset;
+    (word & !mask) | ((value & value_mask(bits)) << offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ new_evm_with_mode, EvmMode };
+    use alloy::primitives::{ address, hex };
+    use alloy::rpc::types::eth::Block;
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode };
+
+    const TOKEN: Address = address!("2222222222222222222222222222222222222222");
+    const ACCOUNT: Address = address!("1111111111111111111111111111111111111111");
+
+    // `balanceOf` reads `balances[owner]` at slot 0 and reverts in block 0, like a token not launched yet
+    const TOKEN_CODE: [u8; 36] = hex!("4315601e576004356000526000602052604060002054600052602060" "00f35b60006000fd");
+
+    #[test]
+    fn deal_in_the_block_of_the_fork() {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(TOKEN, AccountInfo {
+            code: Some(Bytecode::new_raw(TOKEN_CODE.into())),
+            ..Default::default()
+        });
+
+        let mut block = Block::default();
+        block.header.number = Some(1);
+        block.header.gas_limit = 30_000_000;
+        block.header.base_fee_per_gas = Some(1_000_000_000);
+        let mut evm = new_evm_with_mode(ForkDB::offline(db), block, EvmMode::Strict);
+        assert_eq!(evm.db().block_env().number, U256::from(1));
+
+        let amount = U256::from(1000);
+        evm.db_mut().deal(TOKEN, ACCOUNT, amount, false).unwrap();
+
+        let slot = MappingSlot {
+            storage_address: TOKEN,
+            base_slot: U256::ZERO,
+            layout: MappingLayout::Solidity,
+            offset: 0,
+            bits: 256,
+        };
+        assert_eq!(find_balance_slot(evm.db(), TOKEN, ACCOUNT).unwrap(), slot);
+        assert_eq!(evm.db_mut().storage(TOKEN, slot.slot(&[ACCOUNT])).unwrap(), amount);
+    }
+}
@@ -0,0 +1,208 @@
+use alloy::dyn_abi::{ DynSolValue, JsonAbiExt };
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::{ address, Address, Bytes, B256 };
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use std::path::Path;
+
+
+/// Deterministic deployment proxy used by Foundry for CREATE2 deployments
+///
+/// Calldata is `salt ++ init_code`, the proxy deploys the contract with CREATE2 and returns its address
+pub const CREATE2_DEPLOYER: Address = address!("4e59b44847b379578588920cA78FbF26c0B4956C");
+
+/// A compiled contract loaded from a solc, Foundry or Hardhat artifact
+#[derive(Debug, Clone)]
+pub struct ContractArtifact {
+    pub abi: JsonAbi,
+
+    /// Creation bytecode, runs the constructor and returns the runtime code
+    pub bytecode: Bytes,
+
+    /// Runtime bytecode, if the artifact contains it
+    pub deployed_bytecode: Option<Bytes>,
+}
+
+impl ContractArtifact {
+    /// Loads an artifact from a json file, eg. `contracts/SwapRouter.json`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let json = std::fs::read_to_string(path.as_ref())?;
+        Self::from_json(&json)
+    }
+
+    /// Parses an artifact from a json string
+    ///
+    /// Supported layouts:
+    /// - Foundry: `{ "abi": [..], "bytecode": { "object": "0x.." } }`
+    /// - solc standard json: `{ "abi": [..], "evm": { "bytecode": { "object": ".." } } }`
+    /// - Hardhat: `{ "abi": [..], "bytecode": "0x.." }`
+    pub fn from_json(json: &str) -> Result<Self, anyhow::Error> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+
+        let raw: RawArtifact = serde_json::from_str(json)?;
+        let abi = match raw.abi {
+            Some(abi) => serde_json::from_str::<JsonAbi>(abi.get())?,
+            None => JsonAbi::default(),
+        };
+
+        let bytecode = match find_bytecode(&value, "bytecode") {
+            Some(code) => code,
+            None => return Err(anyhow::anyhow!("Artifact does not contain creation bytecode")),
+        };
+        let bytecode = parse_bytecode(bytecode)?;
+
+        // interfaces and abstract contracts have an empty runtime code
+        let deployed_bytecode = match find_bytecode(&value, "deployedBytecode") {
+            Some(code) if code.trim_start_matches("0x").is_empty() => None,
+            Some(code) => Some(parse_bytecode(code)?),
+            None => None,
+        };
+
+        Ok(Self {
+            abi,
+            bytecode,
+            deployed_bytecode,
+        })
+    }
+
+    /// Returns the creation bytecode with the ABI-encoded constructor arguments appended
+    pub fn creation_code(&self, args: &[DynSolValue]) -> Result<Bytes, anyhow::Error> {
+        let encoded_args = match &self.abi.constructor {
+            Some(constructor) => constructor.abi_encode_input(args)?,
+            None if args.is_empty() => Vec::new(),
+            None => return Err(anyhow::anyhow!("Contract has no constructor but arguments were given")),
+        };
+
+        Ok(encode_creation_code(&self.bytecode, &encoded_args))
+    }
+}
+
+// the internal types of `JsonAbi` borrow from the input, it can't be deserialized from a `serde_json::Value`
+#[derive(Deserialize)]
+struct RawArtifact<'a> {
+    #[serde(borrow)]
+    abi: Option<&'a RawValue>,
+}
+
+/// Appends the ABI-encoded constructor arguments to the creation bytecode
+///
+/// Arguments from [alloy::sol_types::SolValue::abi_encode_params] can be passed directly
+pub fn encode_creation_code(bytecode: &Bytes, encoded_args: &[u8]) -> Bytes {
+    let mut code = bytecode.to_vec();
+    code.extend_from_slice(encoded_args);
+    code.into()
+}
+
+/// Computes the address of a contract deployed with `CREATE`
+pub fn create_address(deployer: Address, nonce: u64) -> Address {
+    deployer.create(nonce)
+}
+
+/// Computes the address of a contract deployed with `CREATE2`
+pub fn create2_address(deployer: Address, salt: B256, init_code: &[u8]) -> Address {
+    deployer.create2_from_code(salt, init_code)
+}
+
+/// Builds the calldata for the [CREATE2_DEPLOYER] proxy
+pub fn encode_create2_deploy(salt: B256, init_code: &[u8]) -> Bytes {
+    let mut data = salt.to_vec();
+    data.extend_from_slice(init_code);
+    data.into()
+}
+
+// Foundry and solc nest the code in an `object` field, Hardhat stores it directly
+fn find_bytecode<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    let field = value.get(key).or_else(|| value.get("evm").and_then(|evm| evm.get(key)))?;
+    match field {
+        serde_json::Value::String(code) => Some(code),
+        serde_json::Value::Object(_) => field.get("object").and_then(|o| o.as_str()),
+        _ => None,
+    }
+}
+
+fn parse_bytecode(code: &str) -> Result<Bytes, anyhow::Error> {
+    let code = code.trim_start_matches("0x");
+    if code.is_empty() {
+        return Err(anyhow::anyhow!("Bytecode is empty, the contract may be an interface or abstract"));
+    }
+    if code.contains("__") {
+        return Err(anyhow::anyhow!("Bytecode has unlinked libraries"));
+    }
+    Ok(code.parse()?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+    use crate::EvmMode;
+    use alloy::primitives::{ b256, hex, U256 };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode };
+
+    // runtime code of the deterministic deployment proxy
+    const CREATE2_DEPLOYER_CODE: [u8; 69] = hex!(
+        "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe03601600081602082378035828234f58015156039578182fd5b8082525050506014600cf3"
+    );
+
+    #[test]
+    fn create2_vectors() {
+        // examples of EIP-1014
+        assert_eq!(
+            create2_address(Address::ZERO, B256::ZERO, &hex!("00")),
+            address!("4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38")
+        );
+        assert_eq!(
+            create2_address(
+                address!("deadbeef00000000000000000000000000000000"),
+                b256!("000000000000000000000000feed000000000000000000000000000000000000"),
+                &hex!("00")
+            ),
+            address!("D04116cDd17beBE565EB2422F2497E06cC1C9833")
+        );
+        assert_eq!(
+            create2_address(Address::ZERO, B256::ZERO, &[]),
+            address!("E33C0C7F7df4809055C3ebA6c09CFe4BaF1BD9e0")
+        );
+    }
+
+    #[test]
+    fn swap_router_artifact() {
+        let artifact = ContractArtifact::from_file("contracts/SwapRouter.json").unwrap();
+        assert!(artifact.abi.constructor.is_some());
+        assert!(artifact.abi.function("do_swap").is_some());
+        assert!(artifact.deployed_bytecode.is_some());
+
+        let init_code = artifact.creation_code(&[]).unwrap();
+        assert_eq!(init_code, artifact.bytecode);
+        assert!(artifact.creation_code(&[DynSolValue::Bool(true)]).is_err());
+
+        // the proxy deploys at the predicted address
+        let caller = address!("1111111111111111111111111111111111111111");
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CREATE2_DEPLOYER, AccountInfo {
+            code: Some(Bytecode::new_raw(CREATE2_DEPLOYER_CODE.into())),
+            ..Default::default()
+        });
+        let mut simulator = Simulator::offline(db, EvmMode::Relaxed);
+
+        let salt = B256::with_last_byte(1);
+        let outcome = simulator.deploy_create2(caller, salt, init_code.clone(), U256::ZERO).unwrap();
+        assert!(outcome.is_success());
+
+        let expected = create2_address(CREATE2_DEPLOYER, salt, &init_code);
+        assert_eq!(outcome.output.as_ref(), expected.as_slice());
+        assert_eq!(outcome.created_address, Some(expected));
+    }
+
+    #[test]
+    fn empty_bytecode() {
+        let err = ContractArtifact::from_json(r#"{ "abi": [], "bytecode": "0x", "deployedBytecode": "0x" }"#);
+        assert!(err.is_err());
+
+        let artifact = ContractArtifact::from_json(r#"{ "abi": [], "bytecode": "0x00", "deployedBytecode": "0x" }"#).unwrap();
+        assert!(artifact.deployed_bytecode.is_none());
+    }
+}
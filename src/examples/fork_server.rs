@@ -0,0 +1,42 @@
+use alloy::providers::Provider;
+use alloy::rpc::types::eth::{ BlockId, BlockNumberOrTag };
+use alloy::primitives::utils::parse_ether;
+use revm_by_example::{ forked_db::fork_factory::ForkFactory, rpc_server::{ serve, ForkNode }, * };
+
+use revm::db::{ CacheDB, EmptyDB };
+
+// Serves a fork of the latest block on http://127.0.0.1:8545
+// Try it with `cast block-number --rpc-url http://127.0.0.1:8545`
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let client = get_client().await?;
+
+    let latest_block = client.get_block_number().await?;
+    let block_id = BlockId::Number(BlockNumberOrTag::Number(latest_block));
+    let block = client.get_block(block_id, false.into()).await?.unwrap();
+    let chain_id = client.get_chain_id().await?;
+    let cache_db = CacheDB::new(EmptyDB::default());
+
+    let mut fork_factory = ForkFactory::new_sandbox_factory(
+        client.clone(),
+        cache_db,
+        Some(block_id)
+    );
+
+    // a funded account to send transactions from
+    let account = DummyAccount::builder()
+        .seed("fork-server")?
+        .balance(parse_ether("1000")?)
+        .build();
+    insert_dummy_account(&account, &mut fork_factory)?;
+
+    println!("Funded account: {:?}", account.address);
+    println!("Private key: {}", account.signer.to_bytes());
+
+    let node = ForkNode::new(fork_factory.new_sandbox_fork(), block, chain_id);
+
+    println!("Listening on http://127.0.0.1:8545, forked at block {}", latest_block);
+    serve(node, "127.0.0.1:8545").await?;
+
+    Ok(())
+}
@@ -58,7 +58,7 @@ async fn main() -> Result<(), anyhow::Error> {
 
     // And as expected, the call should revert
     if !res.is_success() {
-        println!("Call Reverted, Reason: {:?}", revert_msg(output));
+        println!("Call Reverted, Reason: {}", decode_revert(output));
     } else {
         println!("Call Successful, This should not happen");}
 
@@ -6,11 +6,15 @@ use futures_util::StreamExt;
 
 use revm_by_example::{
     forked_db::fork_factory::ForkFactory,
+    inspectors::{ CallFrame, CallTracerConfig },
+    simulator::Simulator,
+    tx_env::tx_env_from_rpc,
+    utils::{ SignatureDb, TokenRegistry },
+    validation::TxRejection,
     *,
 };
 
 use revm::db::{ CacheDB, EmptyDB };
-use revm::primitives::{ Bytes, TransactTo };
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -32,36 +36,64 @@ async fn main() -> Result<(), anyhow::Error> {
         cache_db.clone(),
         Some(block_id)
     );
-    let fork_db = fork_factory.new_sandbox_fork();
+    let mut fork_db = fork_factory.new_sandbox_fork();
+
+    // token metadata is resolved on the fork once per token,
+    // use TokenRegistry::open to keep it in a file between runs
+    let mut tokens = TokenRegistry::new();
 
     while let Some(tx) = stream.next().await {
         {
             
             
-            let mut evm = new_evm(fork_db.clone(), block.clone().unwrap());
-
-            evm.tx_mut().caller = tx.from.0.into();
-            evm.tx_mut().transact_to = TransactTo::Call(tx.to.unwrap_or_default().0.into());
-            evm.tx_mut().data = Bytes::from(tx.input.0);
-            evm.tx_mut().value = tx.value;
-
-            let res = evm.transact()?;
-            let touched_accs = res.state.keys();
-            let touched_pools: Vec<Address> = touched_accs
-                .clone()
-                .into_iter()
-                .filter(|acc| pools.contains(acc))
-                .map(|acc| *acc)
+            let mut simulator = Simulator::new(fork_db.clone(), block.clone().unwrap());
+            let config = CallTracerConfig { only_top_call: false, with_log: false };
+
+            // pending transactions queued behind others have a nonce too high for the fork
+            let (_, trace) = match simulator.call_trace(tx_env_from_rpc(&tx), config) {
+                Ok(result) => result,
+                Err(e) if e.is::<TxRejection>() => {
+                    println!("Tx {:?} skipped: {}", tx.hash, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // every call into a pool, with the contract that made it
+            let pool_calls: Vec<&CallFrame> = trace
+                .iter()
+                .filter(|frame| frame.to.map(|to| pools.contains(&to)).unwrap_or(false))
                 .collect();
-           
-            if !touched_pools.is_empty() {
-                let output = format!(
-                    "Tx Touched pools: {:?}
-                View on Etherscan https://etherscan.io/tx/{:?}",
-                    touched_pools,
-                    tx.hash
-                );
-                println!("{}", output);
+
+            if !pool_calls.is_empty() {
+                println!("Tx {:?} touched pools", tx.hash);
+                for frame in pool_calls {
+                    println!(
+                        "  {:?} {:?} -> {:?} {} gas used: {} {}",
+                        frame.kind,
+                        frame.from,
+                        frame.to.unwrap_or_default(),
+                        SignatureDb::global().label_call(&frame.input),
+                        frame.gas_used,
+                        frame.error.as_deref().unwrap_or("")
+                    );
+                }
+
+                // net balance changes, the first thing to look at when vetting the transaction
+                let (_, changes) = match simulator.asset_changes(tx_env_from_rpc(&tx)) {
+                    Ok(result) => result,
+                    Err(e) if e.is::<TxRejection>() => {
+                        println!("Tx {:?} skipped: {}", tx.hash, e);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                for token in changes.tokens() {
+                    let _ = tokens.resolve(token, &mut fork_db);
+                }
+                print!("{}", changes.to_report(&tokens.tokens()));
+
+                println!("View on Etherscan https://etherscan.io/tx/{:?}", tx.hash);
             }
         }
     }
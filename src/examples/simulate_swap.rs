@@ -28,7 +28,13 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let one_eth = parse_ether("1")?;
     let dummy_contract = DummyAccount::new(AccountType::Contract(swap_router_bytecode()), U256::ZERO, U256::ZERO);
-    let dummy_account = DummyAccount::new(AccountType::EOA, one_eth, one_eth);
+
+    // fund the account with 1 ETH and 1 WETH and approve the contract to spend the WETH
+    let dummy_account = DummyAccount::builder()
+        .balance(one_eth)
+        .token_balance(*WETH, one_eth)
+        .approval(*WETH, dummy_contract.address, one_eth)
+        .build();
 
     insert_dummy_account(&dummy_contract, &mut fork_factory)?;
     insert_dummy_account(&dummy_account, &mut fork_factory)?;
@@ -56,23 +62,11 @@ async fn main() -> Result<(), anyhow::Error> {
         minimum_received: U256::ZERO // no slipage
     };
 
-    // Approve the contract to spend 1 WETH
-    let call_data = weth.encode_approve(dummy_contract.address, one_eth);
-
-    evm.tx_mut().caller = dummy_account.address;
-    evm.tx_mut().transact_to = TransactTo::Call(weth.address);
-    evm.tx_mut().data = call_data.into();
-    evm.tx_mut().value = U256::ZERO;
-
-
-    let res = evm.transact_commit()?;
-    let output = res.output().unwrap_or_default();
-
-    ensure!(res.is_success(), "Approve call reverted, Reason: {:?}", revert_msg(&output));
-
     // ** Simulate a WETH/USDC swap on Uniswap V3
     let call_data = encode_swap(swap_params);
 
+    evm.tx_mut().caller = dummy_account.address;
+    evm.tx_mut().value = U256::ZERO;
     evm.tx_mut().transact_to = TransactTo::Call(dummy_contract.address);
     evm.tx_mut().data = call_data.into();
 
@@ -86,6 +80,11 @@ async fn main() -> Result<(), anyhow::Error> {
     ensure!(amount_out > U256::ZERO, "Amount out is zero");
     println!("Swapped {} for {}", to_readable(one_eth, weth), to_readable(amount_out, usdc));
 
+    // the events show what actually moved, not only what the router returned
+    for log in decode_logs(res.logs()) {
+        println!("{:?} emitted {:?}", log.address, log.event);
+    }
+
 
     Ok(())
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use futures::channel::mpsc::{SendError, TrySendError};
+use std::convert::Infallible;
 use std::sync::{mpsc::RecvError, Arc};
 
 #[allow(dead_code)]
@@ -45,5 +46,12 @@ impl DatabaseError {
     }
 }
 
+// the error of the in memory CacheDB<EmptyDB>
+impl From<Infallible> for DatabaseError {
+    fn from(err: Infallible) -> Self {
+        match err {}
+    }
+}
+
 // Result alias with `DatabaseError` as error
 pub type DatabaseResult<T> = Result<T, DatabaseError>;
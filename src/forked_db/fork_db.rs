@@ -1,10 +1,11 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::channel as oneshot_channel;
 
 use futures::channel::mpsc::Sender;
 use revm::{
     db::{CacheDB, DatabaseRef, EmptyDB},
     primitives::{
-        Account, AccountInfo, Address, Bytecode, HashMap,
+        Account, AccountInfo, Address, BlockEnv, Bytecode, HashMap,
          B256, KECCAK_EMPTY, U256,
     },
     Database, DatabaseCommit,
@@ -14,17 +15,92 @@ use super::{
     database_error::{DatabaseError, DatabaseResult},
     BackendFetchRequest,
 };
+use crate::cheats::{allowance_storage, deal_storage};
+
+static NEXT_FORK_ID: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn next_fork_id() -> u64 {
+    NEXT_FORK_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Clone, Debug)]
 pub struct ForkDB {
     // used to make calls for missing data
     backend: Sender<BackendFetchRequest>,
     pub db: CacheDB<EmptyDB>,
+    // shared by the clones and by the forks of the same `ForkFactory`
+    fork_id: u64,
+    // block the calls of the cheats run in
+    block_env: BlockEnv,
 }
 
 impl ForkDB {
     pub fn new(backend: Sender<BackendFetchRequest>, db: CacheDB<EmptyDB>) -> Self {
-        Self { backend, db }
+        Self { backend, db, fork_id: next_fork_id(), block_env: BlockEnv::default() }
+    }
+
+    pub(crate) fn with_fork_id(mut self, fork_id: u64) -> Self {
+        self.fork_id = fork_id;
+        self
+    }
+
+    /// The block the cheats make their calls in, set by [crate::new_evm_with_mode]
+    pub fn block_env(&self) -> &BlockEnv {
+        &self.block_env
+    }
+
+    /// Sets the block the cheats make their calls in, eg. for tokens that read `block.number`
+    pub fn set_block_env(&mut self, block_env: BlockEnv) {
+        self.block_env = block_env;
+    }
+
+    /// Identifies the fork this db fetches its state from, the storage slots found by the cheats are cached per fork
+    pub fn fork_id(&self) -> u64 {
+        self.fork_id
+    }
+
+    /// Sets the ERC20 balance of `account` directly in storage, like Foundry's `deal`
+    ///
+    /// If `adjust_total_supply` is true `totalSupply` is updated too, unless the token doesn't keep it
+    /// in storage like WETH9 whose supply is its ETH balance
+    pub fn deal(
+        &mut self,
+        token: Address,
+        account: Address,
+        amount: U256,
+        adjust_total_supply: bool,
+    ) -> Result<(), anyhow::Error> {
+        for (address, slot, value) in deal_storage(self, token, account, amount, adjust_total_supply)? {
+            self.insert_account_storage(address, slot, value)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the ERC20 allowance of `spender` over the tokens of `owner` directly in storage
+    pub fn set_allowance(
+        &mut self,
+        token: Address,
+        owner: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Result<(), anyhow::Error> {
+        let (address, slot, value) = allowance_storage(self, token, owner, spender, amount)?;
+        self.insert_account_storage(address, slot, value)?;
+        Ok(())
+    }
+
+    /// Writes a storage slot of an account
+    ///
+    /// The account info is fetched first, so the code and balance of a forked contract are kept
+    pub fn insert_account_storage(
+        &mut self,
+        address: Address,
+        slot: U256,
+        value: U256,
+    ) -> DatabaseResult<()> {
+        self.basic(address)?;
+        self.db.insert_account_storage(address, slot, value)?;
+        Ok(())
     }
 
     fn do_get_basic(&self, address: Address) -> DatabaseResult<Option<AccountInfo>> {
@@ -89,14 +165,16 @@ impl Database for ForkDB {
             }
         }
 
-        // get account info
-        let acc_info = match self.do_get_basic(address) {
-            Ok(a) => a,
-            Err(e) => return Err(e),
-        };
+        // get account info if we dont have it, a local account (eg. a deployed contract) must not be overwritten
+        if !self.db.accounts.contains_key(&address) {
+            let acc_info = match self.do_get_basic(address) {
+                Ok(a) => a,
+                Err(e) => return Err(e),
+            };
 
-        if let Some(a) = acc_info {
-            self.db.insert_account_info(address, a);
+            if let Some(a) = acc_info {
+                self.db.insert_account_info(address, a);
+            }
         }
 
         // make rpc call to fetch storage
@@ -203,3 +281,31 @@ impl DatabaseCommit for ForkDB {
         self.db.commit(changes)
     }
 }
+
+#[cfg(test)]
+impl ForkDB {
+    // A fork without a node for the tests, missing accounts are empty and missing slots are zero
+    pub(crate) fn offline(db: CacheDB<EmptyDB>) -> Self {
+        use futures::StreamExt;
+
+        let (backend, mut requests) = futures::channel::mpsc::channel::<BackendFetchRequest>(16);
+        std::thread::spawn(move || {
+            futures::executor::block_on(async move {
+                while let Some(request) = requests.next().await {
+                    match request {
+                        BackendFetchRequest::Basic(_, sender) => {
+                            let _ = sender.send(Ok(AccountInfo::default()));
+                        }
+                        BackendFetchRequest::Storage(_, _, sender) => {
+                            let _ = sender.send(Ok(U256::ZERO));
+                        }
+                        BackendFetchRequest::BlockHash(_, sender) => {
+                            let _ = sender.send(Ok(B256::ZERO));
+                        }
+                    }
+                }
+            })
+        });
+        Self::new(backend, db)
+    }
+}
@@ -4,15 +4,16 @@ use std::sync::Arc;
 use alloy::providers::RootProvider;
 use super::{
     database_error::DatabaseResult,
-    fork_db::ForkDB,
+    fork_db::{next_fork_id, ForkDB},
     global_backend::{BackendFetchRequest, GlobalBackend},
 };
 
+use crate::cheats::{allowance_storage, deal_storage};
 use alloy::rpc::types::eth::BlockId;
 use futures::channel::mpsc::{channel, Sender};
 use revm::{
     db::{CacheDB, EmptyDB},
-    primitives::{AccountInfo, Address as rAddress, U256 as rU256},
+    primitives::{AccountInfo, Address as rAddress, BlockEnv, U256 as rU256},
 };
 
 /// Type that setups up backend and clients to talk to backend
@@ -22,6 +23,10 @@ use revm::{
 pub struct ForkFactory {
     backend: Sender<BackendFetchRequest>,
     initial_db: CacheDB<EmptyDB>,
+    // given to every ForkDB of this factory
+    fork_id: u64,
+    // given to every ForkDB of this factory, used by `deal` and `set_allowance`
+    block_env: BlockEnv,
 }
 
 impl ForkFactory {
@@ -45,6 +50,8 @@ impl ForkFactory {
             Self {
                 backend,
                 initial_db,
+                fork_id: next_fork_id(),
+                block_env: BlockEnv::default(),
             },
             handler,
         )
@@ -89,7 +96,14 @@ impl ForkFactory {
 
     // Creates new ForkDB that fallsback on this `ForkFactory` instance
     pub fn new_sandbox_fork(&self) -> ForkDB {
-        ForkDB::new(self.backend.clone(), self.initial_db.clone())
+        let mut fork_db = ForkDB::new(self.backend.clone(), self.initial_db.clone()).with_fork_id(self.fork_id);
+        fork_db.set_block_env(self.block_env.clone());
+        fork_db
+    }
+
+    /// Sets the block the cheats of this factory and of its forks make their calls in
+    pub fn set_block_env(&mut self, block_env: BlockEnv) {
+        self.block_env = block_env;
     }
 
     #[allow(dead_code)]
@@ -119,6 +133,38 @@ impl ForkFactory {
         Ok(())
     }
 
+    /// Sets the ERC20 balance of `account` directly in storage, like Foundry's `deal`
+    ///
+    /// If `adjust_total_supply` is true `totalSupply` is updated too, unless the token doesn't keep it
+    /// in storage like WETH9 whose supply is its ETH balance
+    pub fn deal(
+        &mut self,
+        token: rAddress,
+        account: rAddress,
+        amount: rU256,
+        adjust_total_supply: bool,
+    ) -> Result<(), anyhow::Error> {
+        let fork_db = self.new_sandbox_fork();
+        for (address, slot, value) in deal_storage(&fork_db, token, account, amount, adjust_total_supply)? {
+            self.insert_account_storage(address, slot, value)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the ERC20 allowance of `spender` over the tokens of `owner` directly in storage
+    pub fn set_allowance(
+        &mut self,
+        token: rAddress,
+        owner: rAddress,
+        spender: rAddress,
+        amount: rU256,
+    ) -> Result<(), anyhow::Error> {
+        let fork_db = self.new_sandbox_fork();
+        let (address, slot, value) = allowance_storage(&fork_db, token, owner, spender, amount)?;
+        self.insert_account_storage(address, slot, value)?;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     // Insert account basic info into local db
     pub fn insert_account_info(&mut self, address: rAddress, info: AccountInfo) {
@@ -0,0 +1,71 @@
+use alloy::eips::eip2930::{ AccessList, AccessListItem };
+use alloy::primitives::{ Address, B256 };
+use hashbrown::HashSet;
+use revm::{ interpreter::{ opcode, Interpreter }, Database, EvmContext, Inspector };
+
+use std::collections::{ BTreeMap, BTreeSet };
+
+
+/// Records every address and storage slot touched by a transaction to build an EIP-2930 access list
+///
+/// `excluded` addresses are left out of the list when no storage slot of theirs was touched,
+/// eg. the sender, the recipient and the precompiles are warm anyway but their storage keys still save gas
+#[derive(Debug, Clone, Default)]
+pub struct AccessListInspector {
+    pub excluded: HashSet<Address>,
+
+    /// address -> storage slots, sorted so the list is deterministic
+    pub touched: BTreeMap<Address, BTreeSet<B256>>,
+}
+
+impl AccessListInspector {
+    pub fn new(excluded: impl IntoIterator<Item = Address>) -> Self {
+        Self {
+            excluded: excluded.into_iter().collect(),
+            touched: BTreeMap::new(),
+        }
+    }
+
+    pub fn access_list(&self) -> AccessList {
+        let items = self.touched
+            .iter()
+            .filter(|(address, slots)| !slots.is_empty() || !self.excluded.contains(*address))
+            .map(|(address, slots)| AccessListItem {
+                address: *address,
+                storage_keys: slots.iter().copied().collect(),
+            })
+            .collect();
+        AccessList(items)
+    }
+
+    fn touch_address(&mut self, address: Address) {
+        self.touched.entry(address).or_default();
+    }
+}
+
+impl<DB: Database> Inspector<DB> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        match interp.current_opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                if let Ok(slot) = interp.stack().peek(0) {
+                    self.touched.entry(interp.contract.target_address).or_default().insert(slot.into());
+                }
+            }
+            opcode::BALANCE |
+            opcode::EXTCODESIZE |
+            opcode::EXTCODECOPY |
+            opcode::EXTCODEHASH |
+            opcode::SELFDESTRUCT => {
+                if let Ok(address) = interp.stack().peek(0) {
+                    self.touch_address(Address::from_word(address.into()));
+                }
+            }
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                if let Ok(address) = interp.stack().peek(1) {
+                    self.touch_address(Address::from_word(address.into()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
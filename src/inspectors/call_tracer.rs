@@ -0,0 +1,429 @@
+use alloy::primitives::{ Address, Bytes, Log, B256, U256, U64 };
+use revm::interpreter::{
+    CallInputs,
+    CallOutcome,
+    CallScheme,
+    CreateInputs,
+    CreateOutcome,
+    CreateScheme,
+    InstructionResult,
+};
+use revm::{ Database, EvmContext, Inspector };
+use serde::{ Deserialize, Serialize };
+
+use crate::utils::{ decode_revert, panic_reason, RevertReason };
+
+
+/// Options of geth's `callTracer`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CallTracerConfig {
+    /// Only record the top level call, without its subcalls
+    pub only_top_call: bool,
+
+    /// Record the logs emitted by each call frame
+    pub with_log: bool,
+}
+
+/// The type of a call frame, serialized like geth does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CallKind {
+    Call,
+    StaticCall,
+    DelegateCall,
+    CallCode,
+    Create,
+    Create2,
+    SelfDestruct,
+}
+
+impl CallKind {
+    pub fn is_create(&self) -> bool {
+        matches!(self, CallKind::Create | CallKind::Create2)
+    }
+}
+
+impl From<CallScheme> for CallKind {
+    fn from(scheme: CallScheme) -> Self {
+        match scheme {
+            CallScheme::Call | CallScheme::ExtCall => CallKind::Call,
+            CallScheme::StaticCall | CallScheme::ExtStaticCall => CallKind::StaticCall,
+            CallScheme::DelegateCall | CallScheme::ExtDelegateCall => CallKind::DelegateCall,
+            CallScheme::CallCode => CallKind::CallCode,
+        }
+    }
+}
+
+impl From<CreateScheme> for CallKind {
+    fn from(scheme: CreateScheme) -> Self {
+        match scheme {
+            CreateScheme::Create => CallKind::Create,
+            CreateScheme::Create2 { .. } => CallKind::Create2,
+        }
+    }
+}
+
+/// A log emitted by a call frame, only recorded with [CallTracerConfig::with_log]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallLog {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+
+    /// Number of subcalls made by the frame before the log was emitted
+    pub position: U64,
+}
+
+/// A call frame of the call tree, the JSON has the same shape as geth's `callTracer` output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    pub from: Address,
+    pub gas: U64,
+    pub gas_used: U64,
+
+    /// Not set for failed contract creations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+
+    pub input: Bytes,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Bytes>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<CallLog>,
+
+    /// Not set for `STATICCALL`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<U256>,
+
+    #[serde(rename = "type")]
+    pub kind: CallKind,
+}
+
+impl CallFrame {
+    fn new(kind: CallKind, from: Address, to: Option<Address>, input: Bytes, gas: u64, value: Option<U256>) -> Self {
+        Self {
+            from,
+            gas: U64::from(gas),
+            gas_used: U64::ZERO,
+            to,
+            input,
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+            logs: Vec::new(),
+            value,
+            kind,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The innermost failed call the failure of this frame comes from, `None` if this frame succeeded
+    ///
+    /// The failure is followed down while the last subcall of a frame failed, a frame that
+    /// made other calls after a failed one handled that failure itself
+    pub fn innermost_revert(&self) -> Option<&CallFrame> {
+        if self.is_success() {
+            return None;
+        }
+        let mut frame = self;
+        while let Some(call) = frame.calls.last().filter(|call| !call.is_success()) {
+            frame = call;
+        }
+        Some(frame)
+    }
+
+    /// Iterates over this frame and all its subcalls, depth first
+    pub fn iter(&self) -> impl Iterator<Item = &CallFrame> {
+        let mut frames = vec![self];
+        std::iter::from_fn(move || {
+            let frame = frames.pop()?;
+            frames.extend(frame.calls.iter().rev());
+            Some(frame)
+        })
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    // geth drops the logs of reverted frames, including the ones of their subcalls
+    fn clear_logs(&mut self) {
+        self.logs.clear();
+        for call in &mut self.calls {
+            call.clear_logs();
+        }
+    }
+
+    fn set_result(&mut self, result: InstructionResult, output: &Bytes, gas_used: u64) {
+        self.gas_used = U64::from(gas_used);
+
+        if result.is_ok() {
+            if !output.is_empty() {
+                self.output = Some(output.clone());
+            }
+            return;
+        }
+
+        self.error = Some(frame_error(result));
+        self.clear_logs();
+        if self.kind.is_create() {
+            self.to = None;
+        }
+
+        // like geth the output is only kept for reverts
+        if result.is_revert() && !output.is_empty() {
+            self.output = Some(output.clone());
+            self.revert_reason = revert_reason(output);
+        }
+    }
+}
+
+/// Records the call tree of a transaction like geth's `callTracer`
+///
+/// Every call frame has its type, from, to, value, gas, gasUsed, input, output, error and revert reason
+/// and with [CallTracerConfig::with_log] the logs it emitted
+#[derive(Debug, Clone, Default)]
+pub struct CallTracer {
+    pub config: CallTracerConfig,
+
+    // frames that have not returned yet, the last one is the current frame
+    stack: Vec<CallFrame>,
+
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    pub fn new(config: CallTracerConfig) -> Self {
+        Self {
+            config,
+            stack: Vec::new(),
+            root: None,
+        }
+    }
+
+    /// Returns the call tree, `None` if the transaction was rejected before any call was made
+    ///
+    /// Like geth the top level frame reports the gas limit and the gas used of the whole transaction
+    pub fn into_frame(self, gas_limit: u64, gas_used: u64) -> Option<CallFrame> {
+        let mut root = self.root?;
+        root.gas = U64::from(gas_limit);
+        root.gas_used = U64::from(gas_used);
+        Some(root)
+    }
+
+    fn push_frame(&mut self, frame: CallFrame) {
+        self.stack.push(frame);
+    }
+
+    fn pop_frame(&mut self, result: InstructionResult, output: &Bytes, gas_used: u64, created: Option<Address>) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+        if created.is_some() {
+            frame.to = created;
+        }
+        frame.set_result(result, output, gas_used);
+        self.add_frame(frame);
+    }
+
+    fn add_frame(&mut self, frame: CallFrame) {
+        match self.stack.last_mut() {
+            Some(parent) => {
+                if !self.config.only_top_call {
+                    parent.calls.push(frame);
+                }
+            }
+            None => {
+                self.root = Some(frame);
+            }
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn log(&mut self, _context: &mut EvmContext<DB>, log: &Log) {
+        if !self.config.with_log || (self.config.only_top_call && self.stack.len() > 1) {
+            return;
+        }
+
+        if let Some(frame) = self.stack.last_mut() {
+            frame.logs.push(CallLog {
+                address: log.address,
+                topics: log.data.topics().to_vec(),
+                data: log.data.data.clone(),
+                position: U64::from(frame.calls.len()),
+            });
+        }
+    }
+
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let kind = CallKind::from(inputs.scheme);
+
+        // delegate calls report the value of the parent frame
+        let value = match kind {
+            CallKind::StaticCall => None,
+            _ => Some(inputs.value.get()),
+        };
+
+        self.push_frame(
+            CallFrame::new(
+                kind,
+                call_from(inputs),
+                Some(inputs.bytecode_address),
+                inputs.input.clone(),
+                inputs.gas_limit,
+                value
+            )
+        );
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.pop_frame(*outcome.instruction_result(), outcome.output(), outcome.gas().spent(), None);
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let kind = CallKind::from(inputs.scheme);
+
+        self.push_frame(
+            CallFrame::new(
+                kind,
+                inputs.caller,
+                None,
+                inputs.init_code.clone(),
+                inputs.gas_limit,
+                Some(inputs.value)
+            )
+        );
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        outcome: CreateOutcome
+    ) -> CreateOutcome {
+        self.pop_frame(*outcome.instruction_result(), outcome.output(), outcome.gas().spent(), outcome.address);
+        outcome
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        let frame = CallFrame::new(CallKind::SelfDestruct, contract, Some(target), Bytes::new(), 0, Some(value));
+        self.add_frame(frame);
+    }
+}
+
+// The contract making the call, the caller of a delegate call is the sender of the parent frame
+pub(crate) fn call_from(inputs: &CallInputs) -> Address {
+    match inputs.scheme {
+        CallScheme::DelegateCall | CallScheme::ExtDelegateCall => inputs.target_address,
+        _ => inputs.caller,
+    }
+}
+
+// The error messages of geth for a failed call frame
+pub(crate) fn frame_error(result: InstructionResult) -> String {
+    let msg = match result {
+        InstructionResult::Revert => "execution reverted",
+        InstructionResult::CallTooDeep => "max call depth exceeded",
+        InstructionResult::OutOfFunds => "insufficient balance for transfer",
+        InstructionResult::OutOfGas |
+        InstructionResult::MemoryOOG |
+        InstructionResult::MemoryLimitOOG |
+        InstructionResult::PrecompileOOG |
+        InstructionResult::InvalidOperandOOG => "out of gas",
+        InstructionResult::OpcodeNotFound | InstructionResult::InvalidFEOpcode => "invalid opcode",
+        InstructionResult::CallNotAllowedInsideStatic |
+        InstructionResult::StateChangeDuringStaticCall => "write protection",
+        InstructionResult::InvalidJump => "invalid jump destination",
+        InstructionResult::StackUnderflow => "stack underflow",
+        InstructionResult::StackOverflow => "stack limit reached 1024",
+        InstructionResult::OutOfOffset => "return data out of bounds",
+        InstructionResult::CreateCollision => "contract address collision",
+        InstructionResult::NonceOverflow => "nonce uint64 overflow",
+        InstructionResult::CreateContractSizeLimit => "max code size exceeded",
+        InstructionResult::CreateInitCodeSizeLimit => "max initcode size exceeded",
+        InstructionResult::CreateContractStartingWithEF => "invalid code: must not begin with 0xef",
+        InstructionResult::PrecompileError => "precompiled contract failed",
+        InstructionResult::OverflowPayment => "gas uint64 overflow",
+        other => return format!("{:?}", other),
+    };
+    msg.to_string()
+}
+
+// geth decodes `Error(string)` and `Panic(uint256)` into the revert reason
+fn revert_reason(output: &Bytes) -> Option<String> {
+    match decode_revert(output) {
+        RevertReason::Error(reason) => Some(reason),
+        RevertReason::Panic(code) => Some(panic_reason(code)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+    use crate::EvmMode;
+    use alloy::primitives::{ address, hex };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode, TransactTo, TxEnv };
+
+    const EOA: Address = address!("1111111111111111111111111111111111111111");
+    const A: Address = address!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    const B: Address = address!("bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb");
+
+    #[test]
+    fn delegate_call_from_the_delegating_contract() {
+        // DELEGATECALL(gas, B, 0, 0, 0, 0)
+        let mut code = hex!("6000600060006000").to_vec();
+        code.push(0x73);
+        code.extend_from_slice(B.as_slice());
+        code.extend_from_slice(&hex!("5af400"));
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(A, AccountInfo {
+            code: Some(Bytecode::new_raw(code.into())),
+            ..Default::default()
+        });
+        db.insert_account_info(B, AccountInfo {
+            code: Some(Bytecode::new_raw(hex!("00").into())),
+            ..Default::default()
+        });
+
+        let mut simulator = Simulator::offline(db, EvmMode::Relaxed);
+        let tx = TxEnv {
+            caller: EOA,
+            transact_to: TransactTo::Call(A),
+            gas_limit: 100_000,
+            ..Default::default()
+        };
+        let (_, frame) = simulator.call_trace(tx, CallTracerConfig::default()).unwrap();
+
+        assert_eq!(frame.from, EOA);
+        assert_eq!(frame.to, Some(A));
+        assert_eq!(frame.calls.len(), 1);
+        assert_eq!(frame.calls[0].kind, CallKind::DelegateCall);
+        assert_eq!(frame.calls[0].from, A);
+        assert_eq!(frame.calls[0].to, Some(B));
+    }
+}
@@ -0,0 +1,383 @@
+use alloy::primitives::{ Address, Selector };
+use hashbrown::HashMap;
+use revm::interpreter::{ CallInputs, CallOutcome, CreateInputs, CreateOutcome };
+use revm::{ Database, EvmContext, Inspector };
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::CallKind;
+use crate::utils::SignatureDb;
+
+
+/// Gas spent by a call frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasFrame {
+    /// Address of the executed code, the implementation for delegate calls
+    pub address: Address,
+
+    /// `None` for contract creations and calls without calldata
+    pub selector: Option<Selector>,
+
+    pub kind: CallKind,
+
+    /// Gas spent by the frame and its subcalls
+    pub gas_used: u64,
+
+    pub calls: Vec<GasFrame>,
+}
+
+impl GasFrame {
+    /// Gas spent by the frame itself, without its subcalls
+    pub fn self_gas(&self) -> u64 {
+        let calls_gas: u64 = self.calls.iter().map(|call| call.gas_used).sum();
+        self.gas_used.saturating_sub(calls_gas)
+    }
+
+    /// Iterates over this frame and all its subcalls, depth first
+    pub fn iter(&self) -> impl Iterator<Item = &GasFrame> {
+        let mut frames = vec![self];
+        std::iter::from_fn(move || {
+            let frame = frames.pop()?;
+            frames.extend(frame.calls.iter().rev());
+            Some(frame)
+        })
+    }
+}
+
+/// Gas aggregated over the frames of a contract or a function
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GasStats {
+    pub address: Address,
+
+    /// `None` when aggregated by contract
+    pub selector: Option<Selector>,
+
+    /// True for the constructor when aggregated by function, always false when aggregated by contract
+    pub is_create: bool,
+
+    pub calls: u64,
+
+    /// Gas spent by the frames themselves
+    pub self_gas: u64,
+
+    /// Gas spent by the frames and their subcalls, recursive calls are counted more than once
+    pub total_gas: u64,
+}
+
+/// The gas profile of a transaction, see [GasProfiler]
+#[derive(Debug, Clone)]
+pub struct GasProfile {
+    pub root: GasFrame,
+
+    /// Names shown instead of the addresses, eg. `SwapRouter`
+    pub labels: HashMap<Address, String>,
+}
+
+impl GasProfile {
+    pub fn new(root: GasFrame) -> Self {
+        Self {
+            root,
+            labels: HashMap::new(),
+        }
+    }
+
+    pub fn label(&mut self, address: Address, name: impl Into<String>) {
+        self.labels.insert(address, name.into());
+    }
+
+    /// Gas per contract, most expensive first
+    pub fn by_contract(&self) -> Vec<GasStats> {
+        self.aggregate(|frame| (frame.address, None, false))
+    }
+
+    /// Gas per function of each contract, most expensive first
+    pub fn by_selector(&self) -> Vec<GasStats> {
+        self.aggregate(|frame| (frame.address, frame.selector, frame.kind.is_create()))
+    }
+
+    /// A table of the gas spent per function, most expensive first
+    ///
+    /// Functions found in the global [SignatureDb] are shown by name, the others by selector
+    pub fn summary_table(&self) -> String {
+        let rows: Vec<(String, String, GasStats)> = self
+            .by_selector()
+            .into_iter()
+            .map(|stats| {
+                let contract = self.contract_name(stats.address);
+                let function = function_name(stats.selector, stats.is_create);
+                (contract, function, stats)
+            })
+            .collect();
+
+        let contract_width = rows.iter().map(|(c, _, _)| c.len()).max().unwrap_or_default().max(8);
+        let function_width = rows.iter().map(|(_, f, _)| f.len()).max().unwrap_or_default().max(8);
+
+        let mut table = String::new();
+        let _ = writeln!(
+            table,
+            "{:<cw$}  {:<fw$}  {:>6}  {:>12}  {:>12}",
+            "Contract",
+            "Function",
+            "Calls",
+            "Self gas",
+            "Total gas",
+            cw = contract_width,
+            fw = function_width
+        );
+        for (contract, function, stats) in &rows {
+            let _ = writeln!(
+                table,
+                "{:<cw$}  {:<fw$}  {:>6}  {:>12}  {:>12}",
+                contract,
+                function,
+                stats.calls,
+                stats.self_gas,
+                stats.total_gas,
+                cw = contract_width,
+                fw = function_width
+            );
+        }
+        let _ = writeln!(table, "Total gas used by the calls: {}", self.root.gas_used);
+        table
+    }
+
+    /// The call stacks in the folded format of flamegraph tools, one `frame;frame;frame gas` line per stack
+    ///
+    /// Pipe into `inferno-flamegraph` or `flamegraph.pl` to get an SVG
+    pub fn folded_stacks(&self) -> String {
+        let mut stacks = BTreeMap::new();
+        self.fold(&self.root, String::new(), &mut stacks);
+
+        let mut folded = String::new();
+        for (stack, gas) in stacks {
+            let _ = writeln!(folded, "{} {}", stack, gas);
+        }
+        folded
+    }
+
+    fn fold(&self, frame: &GasFrame, prefix: String, stacks: &mut BTreeMap<String, u64>) {
+        let name = format!("{}::{}", self.contract_name(frame.address), function_name(frame.selector, frame.kind.is_create()));
+        let stack = match prefix.is_empty() {
+            true => name,
+            false => format!("{};{}", prefix, name),
+        };
+
+        // frames that spent no gas of their own are still visible through their subcalls
+        let self_gas = frame.self_gas();
+        if self_gas > 0 {
+            *stacks.entry(stack.clone()).or_default() += self_gas;
+        }
+        for call in &frame.calls {
+            self.fold(call, stack.clone(), stacks);
+        }
+    }
+
+    // keyed by the constructor too, it has no selector like the calls without calldata
+    fn aggregate(&self, key: impl Fn(&GasFrame) -> (Address, Option<Selector>, bool)) -> Vec<GasStats> {
+        let mut stats: HashMap<(Address, Option<Selector>, bool), GasStats> = HashMap::new();
+
+        for frame in self.root.iter() {
+            let (address, selector, is_create) = key(frame);
+            let entry = stats.entry((address, selector, is_create)).or_insert_with(|| GasStats {
+                address,
+                selector,
+                is_create,
+                ..Default::default()
+            });
+            entry.calls += 1;
+            entry.self_gas += frame.self_gas();
+            entry.total_gas += frame.gas_used;
+        }
+
+        let mut stats: Vec<GasStats> = stats.into_values().collect();
+        stats.sort_by(|a, b| b.self_gas.cmp(&a.self_gas).then(a.address.cmp(&b.address)));
+        stats
+    }
+
+    fn contract_name(&self, address: Address) -> String {
+        match self.labels.get(&address) {
+            Some(label) => label.clone(),
+            None => format!("{:?}", address),
+        }
+    }
+}
+
+fn function_name(selector: Option<Selector>, is_create: bool) -> String {
+    match selector {
+        Some(selector) => match SignatureDb::global().function(selector) {
+            Some(function) => function.name.clone(),
+            None => format!("{:?}", selector),
+        },
+        None if is_create => "constructor".to_string(),
+        None => "fallback".to_string(),
+    }
+}
+
+/// Attributes the gas of a transaction to call frames, contracts and 4-byte function selectors
+///
+/// Use [GasProfile::summary_table] for a per function report and [GasProfile::folded_stacks]
+/// for flamegraphs. The gas of the top level frame excludes the intrinsic gas of the transaction
+#[derive(Debug, Clone, Default)]
+pub struct GasProfiler {
+    // frames that have not returned yet
+    stack: Vec<GasFrame>,
+
+    root: Option<GasFrame>,
+}
+
+impl GasProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the gas profile, `None` if the transaction was rejected before any call was made
+    pub fn into_profile(self) -> Option<GasProfile> {
+        self.root.map(GasProfile::new)
+    }
+
+    fn pop_frame(&mut self, gas_used: u64, created: Option<Address>) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+        frame.gas_used = gas_used;
+        if let Some(address) = created {
+            frame.address = address;
+        }
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => {
+                self.root = Some(frame);
+            }
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for GasProfiler {
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let selector = inputs.input.get(..4).map(Selector::from_slice);
+        self.stack.push(GasFrame {
+            address: inputs.bytecode_address,
+            selector,
+            kind: CallKind::from(inputs.scheme),
+            gas_used: 0,
+            calls: Vec::new(),
+        });
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.pop_frame(outcome.gas().spent(), None);
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.stack.push(GasFrame {
+            address: Address::ZERO,
+            selector: None,
+            kind: CallKind::from(inputs.scheme),
+            gas_used: 0,
+            calls: Vec::new(),
+        });
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        outcome: CreateOutcome
+    ) -> CreateOutcome {
+        self.pop_frame(outcome.gas().spent(), outcome.address);
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+    use crate::EvmMode;
+    use alloy::primitives::{ address, hex };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode, TransactTo, TxEnv };
+
+    const EOA: Address = address!("1111111111111111111111111111111111111111");
+    const A: Address = address!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    const B: Address = address!("bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb");
+
+    fn profile(transact_to: TransactTo, data: &[u8]) -> GasProfile {
+        // CALL(gas, B, 0, 0, 0, 0, 0)
+        let mut code = hex!("6000600060006000600073").to_vec();
+        code.extend_from_slice(B.as_slice());
+        code.extend_from_slice(&hex!("5af100"));
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(A, AccountInfo {
+            code: Some(Bytecode::new_raw(code.into())),
+            ..Default::default()
+        });
+        // SSTORE(0, 1)
+        db.insert_account_info(B, AccountInfo {
+            code: Some(Bytecode::new_raw(hex!("600160005500").into())),
+            ..Default::default()
+        });
+
+        let mut simulator = Simulator::offline(db, EvmMode::Relaxed);
+        let tx = TxEnv {
+            caller: EOA,
+            transact_to,
+            data: data.to_vec().into(),
+            gas_limit: 100_000,
+            ..Default::default()
+        };
+        let (outcome, profile) = simulator.profile_gas(tx).unwrap();
+        assert!(outcome.is_success());
+        profile
+    }
+
+    #[test]
+    fn nested_call() {
+        let profile = profile(TransactTo::Call(A), &[]);
+        let root = &profile.root;
+        assert_eq!(root.kind, CallKind::Call);
+        assert_eq!(root.calls.len(), 1);
+
+        let inner = &root.calls[0];
+        assert_eq!(inner.address, B);
+        assert!(inner.calls.is_empty());
+        assert!(inner.gas_used > 20_000);
+        assert_eq!(inner.self_gas(), inner.gas_used);
+
+        // the inclusive gas of a frame is its own gas plus the inclusive gas of its subcalls
+        assert!(root.self_gas() > 0);
+        assert_eq!(root.gas_used, root.self_gas() + inner.gas_used);
+        assert_eq!(root.iter().map(GasFrame::self_gas).sum::<u64>(), root.gas_used);
+
+        let by_contract = profile.by_contract();
+        assert_eq!(by_contract.len(), 2);
+        assert_eq!(by_contract.iter().map(|stats| stats.self_gas).sum::<u64>(), root.gas_used);
+
+        let b = by_contract.iter().find(|stats| stats.address == B).unwrap();
+        assert_eq!((b.calls, b.self_gas, b.total_gas), (1, inner.gas_used, inner.gas_used));
+        let a = by_contract.iter().find(|stats| stats.address == A).unwrap();
+        assert_eq!((a.calls, a.self_gas, a.total_gas), (1, root.self_gas(), root.gas_used));
+
+        assert!(profile.summary_table().contains("fallback"));
+        assert!(!profile.summary_table().contains("constructor"));
+    }
+
+    #[test]
+    fn constructor() {
+        // returns empty runtime code
+        let profile = profile(TransactTo::Create, &hex!("60006000f3"));
+        assert_eq!(profile.root.kind, CallKind::Create);
+        assert_eq!(profile.root.address, EOA.create(0));
+
+        let stats = profile.by_selector();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].is_create);
+        assert!(profile.folded_stacks().contains("::constructor "));
+    }
+}
@@ -0,0 +1,12 @@
+pub mod storage_access;
+pub mod access_list;
+pub mod call_tracer;
+pub mod parity_tracer;
+pub mod struct_logger;
+pub mod gas_profiler;
+pub use storage_access::*;
+pub use access_list::*;
+pub use call_tracer::*;
+pub use parity_tracer::*;
+pub use struct_logger::*;
+pub use gas_profiler::*;
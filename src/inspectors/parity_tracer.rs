@@ -0,0 +1,735 @@
+use alloy::primitives::{ Address, Bytes, B256, U256, U64 };
+use revm::interpreter::{
+    opcode,
+    CallInputs,
+    CallOutcome,
+    CallScheme,
+    CreateInputs,
+    CreateOutcome,
+    InstructionResult,
+    Interpreter,
+    OpCode,
+};
+use revm::primitives::{ AccountInfo, EvmState, KECCAK_EMPTY };
+use revm::{ Database, EvmContext, Inspector };
+use serde::{ Deserialize, Serialize };
+
+use std::collections::BTreeMap;
+
+use super::call_tracer::call_from;
+
+
+/// The kinds of traces of the Parity `trace_*` methods
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TraceType {
+    /// Flat list of the calls, creations and self destructs
+    Trace,
+
+    /// Every executed opcode, nested by call frame
+    VmTrace,
+
+    /// Changes of balances, nonces, code and storage
+    StateDiff,
+}
+
+/// The output of `trace_call` and `trace_replayTransaction`
+///
+/// The traces that were not requested are `null`, or empty for [TraceType::Trace]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceResults {
+    pub output: Bytes,
+    pub state_diff: Option<StateDiff>,
+    pub trace: Vec<TransactionTrace>,
+    pub vm_trace: Option<VmTrace>,
+
+    /// Only set by `trace_replayTransaction`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_hash: Option<B256>,
+}
+
+/// A trace of the flat Parity trace list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionTrace {
+    #[serde(flatten)]
+    pub action: Action,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// `None` for failed calls and self destructs
+    pub result: Option<TraceOutput>,
+
+    /// Number of direct subtraces
+    pub subtraces: usize,
+
+    /// Position of the trace in the call tree, `[]` for the top level call
+    pub trace_address: Vec<usize>,
+}
+
+/// The action of a trace, serialized with its `type`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "action", rename_all = "camelCase")]
+pub enum Action {
+    Call(CallAction),
+    Create(CreateAction),
+    #[serde(rename = "suicide")]
+    SelfDestruct(SelfDestructAction),
+
+    /// Block and uncle rewards, never produced by transaction traces
+    Reward(RewardAction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallType {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallAction {
+    pub from: Address,
+    pub call_type: CallType,
+    pub gas: U64,
+    pub input: Bytes,
+    pub to: Address,
+    pub value: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAction {
+    pub from: Address,
+    pub gas: U64,
+    pub init: Bytes,
+    pub value: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfDestructAction {
+    pub address: Address,
+    pub refund_address: Address,
+    pub balance: U256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RewardType {
+    Block,
+    Uncle,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardAction {
+    pub author: Address,
+    pub reward_type: RewardType,
+    pub value: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TraceOutput {
+    Create(CreateOutput),
+    Call(CallOutput),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallOutput {
+    pub gas_used: U64,
+    pub output: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOutput {
+    pub address: Address,
+    pub code: Bytes,
+    pub gas_used: U64,
+}
+
+/// The opcodes executed by a call frame
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmTrace {
+    pub code: Bytes,
+    pub ops: Vec<VmInstruction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmInstruction {
+    pub pc: usize,
+    pub cost: u64,
+    pub ex: Option<VmExecutedOperation>,
+
+    /// Trace of the call frame started by the opcode
+    pub sub: Option<VmTrace>,
+}
+
+/// The effects of an opcode
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmExecutedOperation {
+    /// Gas left after the opcode
+    pub used: u64,
+
+    /// Stack items pushed by the opcode
+    pub push: Vec<U256>,
+
+    pub mem: Option<MemoryDelta>,
+    pub store: Option<StorageDelta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryDelta {
+    pub off: usize,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageDelta {
+    pub key: U256,
+    pub val: U256,
+}
+
+/// The change of a value, `=` if unchanged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Delta<T> {
+    #[serde(rename = "=")]
+    Unchanged,
+    #[serde(rename = "+")]
+    Added(T),
+    #[serde(rename = "-")]
+    Removed(T),
+    #[serde(rename = "*")]
+    Changed(ChangedType<T>),
+}
+
+impl<T: PartialEq> Delta<T> {
+    fn new(from: T, to: T) -> Self {
+        if from == to { Delta::Unchanged } else { Delta::Changed(ChangedType { from, to }) }
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, Delta::Unchanged)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedType<T> {
+    pub from: T,
+    pub to: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountDiff {
+    pub balance: Delta<U256>,
+    pub code: Delta<Bytes>,
+    pub nonce: Delta<U64>,
+    pub storage: BTreeMap<B256, Delta<B256>>,
+}
+
+/// The accounts changed by a transaction
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StateDiff(pub BTreeMap<Address, AccountDiff>);
+
+impl StateDiff {
+    /// Compares the state changes of a transaction against the state before it
+    ///
+    /// `db` must not have the changes committed yet, it is used to read the previous balances,
+    /// nonces and code. Gas fees are included since `state` is the final state of the transaction
+    pub fn new<DB: Database>(state: &EvmState, db: &mut DB) -> Result<Self, DB::Error> {
+        let mut diff = BTreeMap::new();
+
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+
+            let pre = db.basic(*address)?.unwrap_or_default();
+            let pre_code = match &pre.code {
+                Some(code) => code.original_bytes(),
+                None if pre.code_hash != KECCAK_EMPTY => db.code_by_hash(pre.code_hash)?.original_bytes(),
+                None => Bytes::new(),
+            };
+            let post_code = match &account.info.code {
+                Some(code) => code.original_bytes(),
+                None if account.info.code_hash == pre.code_hash => pre_code.clone(),
+                None => Bytes::new(),
+            };
+
+            let existed = !is_empty(&pre);
+            let exists = !account.is_selfdestructed() && !is_empty(&account.info);
+
+            let account_diff = match (existed, exists) {
+                (false, false) => {
+                    continue;
+                }
+                (false, true) => AccountDiff {
+                    balance: Delta::Added(account.info.balance),
+                    code: Delta::Added(post_code),
+                    nonce: Delta::Added(U64::from(account.info.nonce)),
+                    storage: account.storage
+                        .iter()
+                        .filter(|(_, slot)| !slot.present_value().is_zero())
+                        .map(|(key, slot)| (B256::from(*key), Delta::Added(slot.present_value().into())))
+                        .collect(),
+                },
+                (true, false) => AccountDiff {
+                    balance: Delta::Removed(pre.balance),
+                    code: Delta::Removed(pre_code),
+                    nonce: Delta::Removed(U64::from(pre.nonce)),
+                    storage: account.storage
+                        .iter()
+                        .filter(|(_, slot)| !slot.original_value().is_zero())
+                        .map(|(key, slot)| (B256::from(*key), Delta::Removed(slot.original_value().into())))
+                        .collect(),
+                },
+                (true, true) => AccountDiff {
+                    balance: Delta::new(pre.balance, account.info.balance),
+                    code: Delta::new(pre_code, post_code),
+                    nonce: Delta::new(U64::from(pre.nonce), U64::from(account.info.nonce)),
+                    storage: account.storage
+                        .iter()
+                        .filter(|(_, slot)| slot.is_changed())
+                        .map(|(key, slot)| {
+                            let delta = Delta::new(slot.original_value().into(), slot.present_value().into());
+                            (B256::from(*key), delta)
+                        })
+                        .collect(),
+                },
+            };
+
+            let unchanged = account_diff.balance.is_unchanged() &&
+                account_diff.code.is_unchanged() &&
+                account_diff.nonce.is_unchanged() &&
+                account_diff.storage.is_empty();
+            if !unchanged {
+                diff.insert(*address, account_diff);
+            }
+        }
+
+        Ok(StateDiff(diff))
+    }
+}
+
+fn is_empty(info: &AccountInfo) -> bool {
+    info.balance.is_zero() && info.nonce == 0 && (info.code_hash == KECCAK_EMPTY || info.code_hash.is_zero())
+}
+
+// A trace of the call tree before it is flattened
+#[derive(Debug, Clone)]
+struct TraceNode {
+    action: Action,
+    error: Option<String>,
+    result: Option<TraceOutput>,
+    children: Vec<TraceNode>,
+}
+
+// An opcode waiting for its effects, calls and creations only have them after the subcall returns
+#[derive(Debug, Clone)]
+struct PendingOp {
+    gas_before: u64,
+    mem: Option<(usize, usize)>,
+    store: Option<StorageDelta>,
+    outputs: usize,
+}
+
+/// Records Parity style traces of a transaction, the output of `trace_call` and `trace_replayTransaction`
+///
+/// Only the requested [TraceType::Trace] and [TraceType::VmTrace] are recorded,
+/// the [StateDiff] is built from the state changes with [StateDiff::new]
+#[derive(Debug, Clone, Default)]
+pub struct ParityTracer {
+    record_traces: bool,
+    record_vm_trace: bool,
+
+    // frames that have not returned yet
+    nodes: Vec<TraceNode>,
+    root: Option<TraceNode>,
+
+    vm_traces: Vec<(VmTrace, Option<PendingOp>)>,
+    vm_root: Option<VmTrace>,
+}
+
+impl ParityTracer {
+    pub fn new(trace_types: &[TraceType]) -> Self {
+        Self {
+            record_traces: trace_types.contains(&TraceType::Trace),
+            record_vm_trace: trace_types.contains(&TraceType::VmTrace),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the flat list of traces, parents before their subtraces
+    pub fn traces(&self) -> Vec<TransactionTrace> {
+        let mut traces = Vec::new();
+        if let Some(root) = &self.root {
+            flatten(root, Vec::new(), &mut traces);
+        }
+        traces
+    }
+
+    pub fn into_vm_trace(self) -> Option<VmTrace> {
+        self.vm_root
+    }
+
+    /// Puts together the traces of a finished transaction
+    pub fn into_results(self, output: Bytes, state_diff: Option<StateDiff>) -> TraceResults {
+        TraceResults {
+            output,
+            state_diff,
+            trace: self.traces(),
+            vm_trace: self.into_vm_trace(),
+            transaction_hash: None,
+        }
+    }
+
+    fn push_node(&mut self, action: Action) {
+        if self.record_traces {
+            self.nodes.push(TraceNode { action, error: None, result: None, children: Vec::new() });
+        }
+    }
+
+    fn pop_node(&mut self, result: InstructionResult, output: TraceOutput) {
+        if !self.record_traces {
+            return;
+        }
+        let Some(mut node) = self.nodes.pop() else {
+            return;
+        };
+
+        if result.is_ok() {
+            node.result = Some(output);
+        } else {
+            node.error = Some(trace_error(result));
+        }
+        self.add_node(node);
+    }
+
+    fn add_node(&mut self, node: TraceNode) {
+        match self.nodes.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => {
+                self.root = Some(node);
+            }
+        }
+    }
+
+    fn push_vm_trace(&mut self) {
+        if self.record_vm_trace {
+            self.vm_traces.push((VmTrace::default(), None));
+        }
+    }
+
+    fn pop_vm_trace(&mut self) {
+        if !self.record_vm_trace {
+            return;
+        }
+        let Some((trace, _)) = self.vm_traces.pop() else {
+            return;
+        };
+
+        match self.vm_traces.last_mut() {
+            Some((parent, _)) => {
+                if let Some(op) = parent.ops.last_mut() {
+                    op.sub = Some(trace);
+                }
+            }
+            None => {
+                self.vm_root = Some(trace);
+            }
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for ParityTracer {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if !self.record_vm_trace {
+            return;
+        }
+        let Some((trace, pending)) = self.vm_traces.last_mut() else {
+            return;
+        };
+
+        // the call or creation of the previous opcode has returned
+        if let Some(op) = pending.take() {
+            finish_op(trace, op, interp);
+        }
+
+        if trace.code.is_empty() {
+            trace.code = interp.contract.bytecode.original_bytes();
+        }
+
+        let opcode = interp.current_opcode();
+        let stack = interp.stack();
+        let arg = |n: usize| stack.peek(n).map(|v| v.saturating_to::<usize>()).unwrap_or_default();
+
+        // only memory writes are recorded like in Parity, reads such as MLOAD have no delta
+        let mem = match opcode {
+            opcode::MSTORE => Some((arg(0), 32)),
+            opcode::MSTORE8 => Some((arg(0), 1)),
+            opcode::CALLDATACOPY | opcode::CODECOPY | opcode::RETURNDATACOPY | opcode::MCOPY => {
+                Some((arg(0), arg(2)))
+            }
+            opcode::EXTCODECOPY => Some((arg(1), arg(3))),
+            opcode::CALL | opcode::CALLCODE => Some((arg(5), arg(6))),
+            opcode::DELEGATECALL | opcode::STATICCALL => Some((arg(4), arg(5))),
+            _ => None,
+        };
+
+        let store = match opcode {
+            opcode::SSTORE => {
+                Some(StorageDelta {
+                    key: stack.peek(0).unwrap_or_default(),
+                    val: stack.peek(1).unwrap_or_default(),
+                })
+            }
+            _ => None,
+        };
+
+        trace.ops.push(VmInstruction {
+            pc: interp.program_counter(),
+            cost: 0,
+            ex: None,
+            sub: None,
+        });
+        *pending = Some(PendingOp {
+            gas_before: interp.gas.remaining(),
+            mem,
+            store,
+            outputs: OpCode::new(opcode).map(|op| op.info().outputs() as usize).unwrap_or_default(),
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if !self.record_vm_trace {
+            return;
+        }
+        let Some((trace, pending)) = self.vm_traces.last_mut() else {
+            return;
+        };
+
+        // the effects of calls and creations are known once the subcall returns
+        if interp.next_action.is_call() || interp.next_action.is_create() {
+            if let (Some(op), Some(last)) = (pending.as_ref(), trace.ops.last_mut()) {
+                last.cost = op.gas_before.saturating_sub(interp.gas.remaining());
+            }
+            return;
+        }
+
+        if let Some(op) = pending.take() {
+            finish_op(trace, op, interp);
+        }
+    }
+
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let call_type = match inputs.scheme {
+            CallScheme::Call | CallScheme::ExtCall => CallType::Call,
+            CallScheme::StaticCall | CallScheme::ExtStaticCall => CallType::StaticCall,
+            CallScheme::DelegateCall | CallScheme::ExtDelegateCall => CallType::DelegateCall,
+            CallScheme::CallCode => CallType::CallCode,
+        };
+
+        self.push_node(
+            Action::Call(CallAction {
+                from: call_from(inputs),
+                call_type,
+                gas: U64::from(inputs.gas_limit),
+                input: inputs.input.clone(),
+                to: inputs.bytecode_address,
+                value: inputs.value.get(),
+            })
+        );
+        self.push_vm_trace();
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        let output = TraceOutput::Call(CallOutput {
+            gas_used: U64::from(outcome.gas().spent()),
+            output: outcome.output().clone(),
+        });
+        self.pop_node(*outcome.instruction_result(), output);
+        self.pop_vm_trace();
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.push_node(
+            Action::Create(CreateAction {
+                from: inputs.caller,
+                gas: U64::from(inputs.gas_limit),
+                init: inputs.init_code.clone(),
+                value: inputs.value,
+            })
+        );
+        self.push_vm_trace();
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        outcome: CreateOutcome
+    ) -> CreateOutcome {
+        let output = TraceOutput::Create(CreateOutput {
+            address: outcome.address.unwrap_or_default(),
+            code: outcome.output().clone(),
+            gas_used: U64::from(outcome.gas().spent()),
+        });
+        self.pop_node(*outcome.instruction_result(), output);
+        self.pop_vm_trace();
+        outcome
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        if !self.record_traces {
+            return;
+        }
+        self.add_node(TraceNode {
+            action: Action::SelfDestruct(SelfDestructAction {
+                address: contract,
+                refund_address: target,
+                balance: value,
+            }),
+            error: None,
+            result: None,
+            children: Vec::new(),
+        });
+    }
+}
+
+// Fills the cost and the effects of the last opcode of a frame
+fn finish_op(trace: &mut VmTrace, op: PendingOp, interp: &Interpreter) {
+    let Some(last) = trace.ops.last_mut() else {
+        return;
+    };
+
+    let gas_left = interp.gas.remaining();
+    if last.cost == 0 {
+        last.cost = op.gas_before.saturating_sub(gas_left);
+    }
+
+    // a failed opcode has no effects
+    if interp.instruction_result != InstructionResult::Continue && !interp.instruction_result.is_ok() {
+        return;
+    }
+
+    let stack = interp.stack().data();
+    let outputs = op.outputs.min(stack.len());
+    let mem = op.mem
+        .filter(|(_, size)| *size > 0)
+        .and_then(|(off, size)| {
+            let data = interp.shared_memory.context_memory().get(off..off + size)?;
+            Some(MemoryDelta { off, data: Bytes::copy_from_slice(data) })
+        });
+
+    last.ex = Some(VmExecutedOperation {
+        used: gas_left,
+        push: stack[stack.len() - outputs..].to_vec(),
+        mem,
+        store: op.store,
+    });
+}
+
+fn flatten(node: &TraceNode, trace_address: Vec<usize>, traces: &mut Vec<TransactionTrace>) {
+    traces.push(TransactionTrace {
+        action: node.action.clone(),
+        error: node.error.clone(),
+        result: node.result.clone(),
+        subtraces: node.children.len(),
+        trace_address: trace_address.clone(),
+    });
+
+    for (i, child) in node.children.iter().enumerate() {
+        let mut child_address = trace_address.clone();
+        child_address.push(i);
+        flatten(child, child_address, traces);
+    }
+}
+
+// The error messages of Parity for a failed trace
+fn trace_error(result: InstructionResult) -> String {
+    let msg = match result {
+        InstructionResult::Revert => "Reverted",
+        InstructionResult::OutOfGas |
+        InstructionResult::MemoryOOG |
+        InstructionResult::MemoryLimitOOG |
+        InstructionResult::PrecompileOOG |
+        InstructionResult::InvalidOperandOOG => "Out of gas",
+        InstructionResult::OpcodeNotFound | InstructionResult::InvalidFEOpcode => "Bad instruction",
+        InstructionResult::InvalidJump => "Bad jump destination",
+        InstructionResult::StackUnderflow => "Stack underflow",
+        InstructionResult::StackOverflow => "Out of stack",
+        InstructionResult::OutOfOffset => "Out of bounds",
+        InstructionResult::CallNotAllowedInsideStatic |
+        InstructionResult::StateChangeDuringStaticCall => "Mutable Call In Static Context",
+        InstructionResult::PrecompileError => "Built-in failed",
+        InstructionResult::CallTooDeep => "Call depth limit exceeded",
+        InstructionResult::OutOfFunds => "Insufficient balance for transfer",
+        InstructionResult::CreateCollision => "Contract address collision",
+        InstructionResult::CreateContractSizeLimit => "Contract code size limit exceeded",
+        other => return format!("{:?}", other),
+    };
+    msg.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Simulator;
+    use crate::EvmMode;
+    use alloy::primitives::{ address, hex };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ Bytecode, TransactTo, TxEnv };
+
+    const EOA: Address = address!("1111111111111111111111111111111111111111");
+    const A: Address = address!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    const B: Address = address!("bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb");
+
+    #[test]
+    fn delegate_call_from_the_delegating_contract() {
+        // DELEGATECALL(gas, B, 0, 0, 0, 0)
+        let mut code = hex!("6000600060006000").to_vec();
+        code.push(0x73);
+        code.extend_from_slice(B.as_slice());
+        code.extend_from_slice(&hex!("5af400"));
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(A, AccountInfo {
+            code: Some(Bytecode::new_raw(code.into())),
+            ..Default::default()
+        });
+        db.insert_account_info(B, AccountInfo {
+            code: Some(Bytecode::new_raw(hex!("00").into())),
+            ..Default::default()
+        });
+
+        let mut simulator = Simulator::offline(db, EvmMode::Relaxed);
+        let tx = TxEnv {
+            caller: EOA,
+            transact_to: TransactTo::Call(A),
+            gas_limit: 100_000,
+            ..Default::default()
+        };
+        let results = simulator.trace_call(tx, &[TraceType::Trace]).unwrap();
+
+        assert_eq!(results.trace.len(), 2);
+        let Action::Call(inner) = &results.trace[1].action else {
+            panic!("expected a call action");
+        };
+        assert_eq!(inner.call_type, CallType::DelegateCall);
+        assert_eq!(inner.from, A);
+        assert_eq!(inner.to, B);
+        assert_eq!(results.trace[1].trace_address, vec![0]);
+    }
+}
@@ -0,0 +1,47 @@
+use alloy::primitives::{ keccak256, Address, B256, U256 };
+use hashbrown::HashMap;
+use revm::{ interpreter::{ opcode, Interpreter }, Database, EvmContext, Inspector };
+
+
+/// Records every storage slot read with `SLOAD`
+/// and the preimages of the 64 byte `KECCAK256` hashes used to compute mapping slots
+#[derive(Debug, Clone, Default)]
+pub struct StorageAccessInspector {
+    /// `(storage address, slot)` in the order they were read
+    pub sloads: Vec<(Address, U256)>,
+
+    /// hash -> the two words that were hashed
+    pub preimages: HashMap<B256, (U256, U256)>,
+}
+
+impl<DB: Database> Inspector<DB> for StorageAccessInspector {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        match interp.current_opcode() {
+            opcode::SLOAD => {
+                if let Ok(slot) = interp.stack().peek(0) {
+                    self.sloads.push((interp.contract.target_address, slot));
+                }
+            }
+            opcode::KECCAK256 => {
+                let (Ok(offset), Ok(size)) = (interp.stack().peek(0), interp.stack().peek(1)) else {
+                    return;
+                };
+
+                // mapping slots are always computed from 2 words
+                if size != U256::from(64) || offset > U256::from(interp.shared_memory.len()) {
+                    return;
+                }
+                let offset = offset.to::<usize>();
+                if offset + 64 > interp.shared_memory.len() {
+                    return;
+                }
+
+                let data = interp.shared_memory.slice(offset, 64);
+                let first = U256::from_be_slice(&data[..32]);
+                let second = U256::from_be_slice(&data[32..]);
+                self.preimages.insert(keccak256(data), (first, second));
+            }
+            _ => {}
+        }
+    }
+}
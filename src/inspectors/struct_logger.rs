@@ -0,0 +1,211 @@
+use alloy::hex;
+use alloy::primitives::{ Address, B256, U256 };
+use revm::interpreter::{ opcode, Interpreter, OpCode };
+use revm::{ Database, EvmContext, Inspector };
+use serde::{ Deserialize, Serialize };
+
+use std::collections::BTreeMap;
+
+use super::call_tracer::frame_error;
+
+
+/// Options of geth's default `debug_traceTransaction` tracer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StructLoggerConfig {
+    pub disable_stack: bool,
+
+    /// Memory is the most expensive part of the trace, it is off by default like in geth
+    pub enable_memory: bool,
+
+    pub disable_storage: bool,
+}
+
+/// A single executed opcode, the JSON has the same shape as geth's `structLogs` entries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: String,
+
+    /// Gas left before the opcode
+    pub gas: u64,
+
+    /// Cost of the opcode, includes the gas sent along with calls
+    pub gas_cost: u64,
+
+    /// Call depth, starts at 1
+    pub depth: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Stack before the opcode, the top is the last item
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Vec<U256>>,
+
+    /// Memory before the opcode in 32 byte words, hex encoded without prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<Vec<String>>,
+
+    /// Storage of the contract accessed so far, only set on `SLOAD` and `SSTORE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<BTreeMap<String, String>>,
+
+    /// Gas refund counter of the transaction
+    #[serde(skip_serializing_if = "is_zero")]
+    pub refund: u64,
+}
+
+/// The output of geth's default tracer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLogResult {
+    pub gas: u64,
+    pub failed: bool,
+
+    /// Return or revert data, hex encoded without prefix
+    pub return_value: String,
+
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// Records every executed opcode like geth's default `debug_traceTransaction` tracer
+///
+/// Useful to compare the execution step by step against a geth node when a simulation diverges
+#[derive(Debug, Clone, Default)]
+pub struct StructLogger {
+    pub config: StructLoggerConfig,
+
+    pub logs: Vec<StructLog>,
+
+    // storage accessed so far by each contract
+    storage: BTreeMap<Address, BTreeMap<B256, B256>>,
+
+    // last seen refund of each call frame, a reverted frame loses its refund
+    refunds: Vec<i64>,
+
+    // the value loaded by SLOAD is only on the stack after the opcode
+    pending_sload: Option<(Address, U256)>,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Puts together the result of a finished transaction
+    pub fn into_result(self, gas_used: u64, failed: bool, output: &[u8]) -> StructLogResult {
+        StructLogResult {
+            gas: gas_used,
+            failed,
+            return_value: hex::encode(output),
+            struct_logs: self.logs,
+        }
+    }
+
+    fn storage_snapshot(&self, address: Address) -> BTreeMap<String, String> {
+        self.storage
+            .get(&address)
+            .map(|slots| {
+                slots
+                    .iter()
+                    .map(|(key, value)| (hex::encode(key), hex::encode(value)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl<DB: Database> Inspector<DB> for StructLogger {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let depth = context.journaled_state.depth();
+        let opcode = interp.current_opcode();
+
+        self.refunds.truncate(depth as usize);
+        self.refunds.resize(depth as usize, 0);
+        if let Some(refund) = self.refunds.last_mut() {
+            *refund = interp.gas.refunded();
+        }
+        let refund = self.refunds.iter().sum::<i64>().max(0) as u64;
+
+        let stack = match self.config.disable_stack {
+            true => None,
+            false => Some(interp.stack().data().clone()),
+        };
+
+        let memory = match self.config.enable_memory {
+            true => Some(interp.shared_memory.context_memory().chunks(32).map(hex::encode).collect()),
+            false => None,
+        };
+
+        let address = interp.contract.target_address;
+        let mut storage = None;
+        if !self.config.disable_storage {
+            match opcode {
+                opcode::SLOAD => {
+                    self.pending_sload = interp.stack().peek(0).ok().map(|key| (address, key));
+                }
+                opcode::SSTORE => {
+                    if let (Ok(key), Ok(value)) = (interp.stack().peek(0), interp.stack().peek(1)) {
+                        self.storage.entry(address).or_default().insert(key.into(), value.into());
+                        storage = Some(self.storage_snapshot(address));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let op = match OpCode::new(opcode) {
+            Some(op) => op.as_str().to_string(),
+            None => format!("opcode {:#x} not defined", opcode),
+        };
+
+        self.logs.push(StructLog {
+            pc: interp.program_counter() as u64,
+            op,
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            depth,
+            error: None,
+            stack,
+            memory,
+            storage,
+            refund,
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let gas_left = interp.gas.remaining();
+        let result = interp.instruction_result;
+
+        let mut storage = None;
+        if let Some((address, key)) = self.pending_sload.take() {
+            if let (false, Ok(value)) = (result.is_error(), interp.stack().peek(0)) {
+                self.storage.entry(address).or_default().insert(key.into(), value.into());
+                storage = Some(self.storage_snapshot(address));
+            }
+        }
+
+        // step_end directly follows its step, before any subcall is executed
+        let Some(log) = self.logs.last_mut() else {
+            return;
+        };
+        log.gas_cost = log.gas.saturating_sub(gas_left);
+
+        // like geth a revert is not an error of the opcode
+        if result.is_error() {
+            log.error = Some(frame_error(result));
+        }
+        if storage.is_some() {
+            log.storage = storage;
+        }
+    }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
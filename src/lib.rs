@@ -1,12 +1,22 @@
 pub mod forked_db;
 pub mod utils;
+pub mod validation;
+pub mod simulator;
+pub mod deploy;
+pub mod abi;
+pub mod inspectors;
+pub mod cheats;
+pub mod tx_env;
+pub mod replay;
+pub mod bundle;
+pub mod rpc_server;
 
 use alloy::{primitives::{ Address, U256 }, providers::{ ProviderBuilder, RootProvider } };
 use alloy::transports::ws::WsConnect;
 use alloy::pubsub::PubSubFrontend;
-use alloy::primitives::keccak256;
 
 use alloy::signers::local::PrivateKeySigner;
+use alloy::primitives::keccak256;
 
 
 use alloy::rpc::types::eth::Block;
@@ -20,6 +30,8 @@ use revm::primitives::{
     Bytecode,
     B256,
     AccountInfo,
+    BlobExcessGasAndPrice,
+    KECCAK_EMPTY,
 };
 use revm::Evm;
 
@@ -45,19 +57,122 @@ pub struct DummyAccount {
     /// ETH balance to fund with
     pub balance: U256,
 
-    /// WETH balance to fund with
+    /// WETH balance to fund with, funded before `token_balances` when not zero
+    #[deprecated(note = "add WETH to `token_balances` instead")]
     pub weth_balance: U256,
 
+    /// `(token, amount)` ERC20 balances to fund with
+    pub token_balances: Vec<(Address, U256)>,
+
+    /// `(token, spender, amount)` ERC20 approvals given by this account
+    pub approvals: Vec<(Address, Address, U256)>,
+
+    /// `(slot, value)` storage of the account, only useful for contracts
+    pub storage: Vec<(U256, U256)>,
+
+    pub nonce: u64,
+
+    /// The private key of the account, can be used to sign transactions
+    pub signer: PrivateKeySigner,
+
     pub address: Address
 }
 
 impl DummyAccount {
+    /// Creates a dummy account with a random key funded with ETH and WETH
+    #[allow(deprecated)]
     pub fn new(account_type: AccountType, balance: U256, weth_balance: U256) -> Self {
-        Self {
-            account_type,
-            balance,
-            weth_balance,
-            address: PrivateKeySigner::random().address()
+        let mut account = DummyAccount::builder()
+            .account_type(account_type)
+            .balance(balance)
+            .build();
+        account.weth_balance = weth_balance;
+        account
+    }
+
+    pub fn builder() -> DummyAccountBuilder {
+        DummyAccountBuilder::default()
+    }
+}
+
+/// Builder for [DummyAccount]
+///
+/// By default the account is an EOA with a random key, zero nonce and no funds
+#[derive(Default)]
+pub struct DummyAccountBuilder {
+    account_type: Option<AccountType>,
+    balance: U256,
+    token_balances: Vec<(Address, U256)>,
+    approvals: Vec<(Address, Address, U256)>,
+    storage: Vec<(U256, U256)>,
+    nonce: u64,
+    signer: Option<PrivateKeySigner>,
+}
+
+impl DummyAccountBuilder {
+    /// Use a fixed private key
+    pub fn private_key(mut self, key: B256) -> Result<Self, anyhow::Error> {
+        self.signer = Some(PrivateKeySigner::from_bytes(&key)?);
+        Ok(self)
+    }
+
+    /// Derive the private key from a seed, the same seed always gives the same account
+    pub fn seed(self, seed: impl AsRef<[u8]>) -> Result<Self, anyhow::Error> {
+        self.private_key(keccak256(seed.as_ref()))
+    }
+
+    pub fn account_type(mut self, account_type: AccountType) -> Self {
+        self.account_type = Some(account_type);
+        self
+    }
+
+    /// Make the account a contract with the given runtime code
+    pub fn contract(self, code: Bytecode) -> Self {
+        self.account_type(AccountType::Contract(code))
+    }
+
+    /// ETH balance to fund with
+    pub fn balance(mut self, balance: U256) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Fund the account with an ERC20 token
+    pub fn token_balance(mut self, token: Address, amount: U256) -> Self {
+        self.token_balances.push((token, amount));
+        self
+    }
+
+    /// Approve `spender` to spend `amount` of `token` on behalf of the account
+    pub fn approval(mut self, token: Address, spender: Address, amount: U256) -> Self {
+        self.approvals.push((token, spender, amount));
+        self
+    }
+
+    /// Set a storage slot of the account
+    pub fn storage(mut self, slot: U256, value: U256) -> Self {
+        self.storage.push((slot, value));
+        self
+    }
+
+    #[allow(deprecated)]
+    pub fn build(self) -> DummyAccount {
+        let signer = self.signer.unwrap_or_else(PrivateKeySigner::random);
+        DummyAccount {
+            account_type: self.account_type.unwrap_or(AccountType::EOA),
+            balance: self.balance,
+            weth_balance: U256::ZERO,
+            token_balances: self.token_balances,
+            approvals: self.approvals,
+            storage: self.storage,
+            nonce: self.nonce,
+            address: signer.address(),
+            signer,
         }
     }
 }
@@ -79,20 +194,68 @@ pub async fn get_client() -> Result<Arc<RootProvider<PubSubFrontend>>, anyhow::E
     Ok(Arc::new(client))
 }
 
+/// How strictly the [Evm] validates transactions before executing them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvmMode {
+    /// Disables the balance, block gas limit and base fee checks for easier testing
+    #[default]
+    Relaxed,
+
+    /// Validates transactions like a node would before including them in a block
+    ///
+    /// Checks the nonce (when `tx.nonce` is set), the balance for `gas * max_fee + value`,
+    /// the fee cap against the block's base fee, the block gas limit and EIP-3607
+    Strict,
+}
+
 /// Creates a new [Evm] instance with initial state from [ForkDB]
 ///
 /// State changes are applied to [Evm]
 pub fn new_evm(fork_db: ForkDB, block: Block) -> Evm<'static, (), ForkDB> {
+    new_evm_with_mode(fork_db, block, EvmMode::Relaxed)
+}
+
+/// Creates a new [Evm] instance with initial state from [ForkDB] validating transactions according to [EvmMode]
+///
+/// Use [validation::validate_tx] to get the reason a transaction would be rejected
+pub fn new_evm_with_mode(fork_db: ForkDB, block: Block, mode: EvmMode) -> Evm<'static, (), ForkDB> {
     let mut evm = Evm::builder().with_db(fork_db).build();
 
     evm.block_mut().number = U256::from(block.header.number.unwrap());
     evm.block_mut().timestamp = U256::from(block.header.timestamp);
     evm.block_mut().coinbase = block.header.miner;
 
-    // Disable some checks for easier testing
-    evm.cfg_mut().disable_balance_check = true;
-    evm.cfg_mut().disable_block_gas_limit = true;
-    evm.cfg_mut().disable_base_fee = true;
+    match mode {
+        EvmMode::Relaxed => {
+            // Disable some checks for easier testing
+            evm.cfg_mut().disable_balance_check = true;
+            evm.cfg_mut().disable_block_gas_limit = true;
+            evm.cfg_mut().disable_base_fee = true;
+        }
+        EvmMode::Strict => {
+            // Use the real block values so the fee and gas limit checks are meaningful
+            evm.block_mut().gas_limit = U256::from(block.header.gas_limit);
+            evm.block_mut().basefee = U256::from(block.header.base_fee_per_gas.unwrap_or_default());
+            // DIFFICULTY returns the difficulty before the merge and prevrandao after it
+            evm.block_mut().difficulty = block.header.difficulty;
+            if let Some(mix_hash) = block.header.mix_hash {
+                evm.block_mut().prevrandao = Some(mix_hash);
+            }
+            if let Some(excess_blob_gas) = block.header.excess_blob_gas {
+                evm.block_mut().blob_excess_gas_and_price = Some(
+                    BlobExcessGasAndPrice::new(excess_blob_gas as u64)
+                );
+            }
+
+            evm.cfg_mut().disable_balance_check = false;
+            evm.cfg_mut().disable_block_gas_limit = false;
+            evm.cfg_mut().disable_base_fee = false;
+            evm.cfg_mut().disable_eip3607 = false;
+        }
+    }
+
+    let block_env = evm.block().clone();
+    evm.db_mut().set_block_env(block_env);
     evm
 }
 
@@ -103,49 +266,48 @@ pub fn new_evm(fork_db: ForkDB, block: Block) -> Evm<'static, (), ForkDB> {
 
 
 /// Inserts a dummy account to the local fork enviroment
+///
+/// Sets the account info and storage, funds the ERC20 balances and writes the approvals
 pub fn insert_dummy_account(
     account: &DummyAccount,
     fork_factory: &mut ForkFactory
 ) -> Result<(), anyhow::Error> {
 
-    let code = match &account.account_type {
-        AccountType::EOA => Bytecode::default(),
-        AccountType::Contract(code) => code.clone(),
+    let (code, code_hash) = match &account.account_type {
+        AccountType::EOA => (Bytecode::default(), KECCAK_EMPTY),
+        AccountType::Contract(code) => (code.clone(), code.hash_slow()),
     };
 
     let account_info = AccountInfo {
         balance: account.balance,
-        nonce: 0,
-        code_hash: B256::default(),
+        nonce: account.nonce,
+        code_hash,
         code: Some(code),
     };
 
     // insert the account info into the fork enviroment
     fork_factory.insert_account_info(account.address, account_info);
 
+    for (slot, value) in &account.storage {
+        fork_factory.insert_account_storage(account.address, *slot, *value)?;
+    }
 
-    // To fund any ERC20 token to an account we need the balance storage slot of the token
-    // For WETH its 3
+    // insert the erc20 token balances to the dummy account
+    // the balance storage slot of each token is discovered by running `balanceOf` on the fork
     // An amazing online tool to see the storage mapping of any contract https://evm.storage/
-    let slot_num = U256::from(3);
-    let addr_padded = pad_left(account.address.to_vec(), 32);
-    let slot = slot_num.to_be_bytes_vec();
-    
-    let data = [&addr_padded, &slot].iter().flat_map(|x| x.iter().copied()).collect::<Vec<u8>>();
-    let slot_hash = keccak256(&data);
-    let slot: U256 = U256::from_be_bytes(slot_hash.try_into().expect("Hash must be 32 bytes"));
-    
+    #[allow(deprecated)]
+    if account.weth_balance > U256::ZERO {
+        fork_factory.deal(*WETH, account.address, account.weth_balance, false)?;
+    }
 
-    // insert the erc20 token balance to the dummy account
-    if let Err(e) = fork_factory.insert_account_storage(*WETH, slot, account.weth_balance) {
-        return Err(anyhow::anyhow!("Failed to insert account storage: {}", e));
+    // zero amounts are written too, eg. to empty the balance of an existing address
+    for (token, amount) in &account.token_balances {
+        fork_factory.deal(*token, account.address, *amount, false)?;
+    }
+
+    for (token, spender, amount) in &account.approvals {
+        fork_factory.set_allowance(*token, account.address, *spender, *amount)?;
     }
 
     Ok(())
 }
-
-fn pad_left(vec: Vec<u8>, full_len: usize) -> Vec<u8> {
-    let mut padded = vec![0u8; full_len - vec.len()];
-    padded.extend(vec);
-    padded
-}
\ No newline at end of file
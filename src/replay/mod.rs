@@ -0,0 +1,412 @@
+use alloy::eips::eip4788::BEACON_ROOTS_ADDRESS;
+use alloy::primitives::{ Log, TxHash, U256 };
+use alloy::providers::{ Provider, RootProvider };
+use alloy::pubsub::PubSubFrontend;
+use alloy::rpc::types::eth::{ Block, BlockId, BlockNumberOrTag, Transaction };
+
+use revm::db::{ CacheDB, EmptyDB };
+use revm::primitives::SpecId;
+use revm::{ inspector_handle_register, Database, DatabaseCommit, Evm, Inspector };
+
+use std::sync::Arc;
+
+use crate::forked_db::{ database_error::DatabaseError, fork_db::ForkDB, fork_factory::ForkFactory };
+use crate::inspectors::{ ParityTracer, StateDiff, TraceResults, TraceType };
+use crate::simulator::SimOutcome;
+use crate::tx_env::tx_env_from_rpc;
+use crate::{ new_evm_with_mode, EvmMode };
+
+
+/// A historical transaction executed again on a fork
+pub struct Replay<I> {
+    pub tx: Transaction,
+
+    /// The block the transaction was included in, with full transactions
+    pub block: Block,
+
+    pub outcome: SimOutcome,
+
+    /// The inspector after tracing the transaction
+    pub inspector: I,
+
+    /// State after the transaction, the replayed transaction is committed
+    pub fork_db: ForkDB,
+}
+
+/// Replays a mined transaction at its exact position in the block, similar to `cast run`
+///
+/// Forks at the parent block, executes every preceding transaction of the block to reproduce
+/// the intra-block state, then runs the target transaction with `inspector` attached.
+/// Transactions are validated like a node would ([EvmMode::Strict]) with the hardfork of the block
+///
+/// Pass `()` as inspector to replay without tracing
+pub async fn replay_transaction<I>(
+    client: Arc<RootProvider<PubSubFrontend>>,
+    hash: TxHash,
+    inspector: I
+) -> Result<Replay<I>, anyhow::Error>
+    where I: Inspector<ForkDB>
+{
+    let (tx, block, evm) = evm_before_transaction(client, hash).await?;
+
+    let mut evm = evm
+        .modify()
+        .reset_handler_with_external_context(inspector)
+        .append_handler_register(inspector_handle_register)
+        .build();
+
+    *evm.tx_mut() = tx_env_from_rpc(&tx);
+    let res = evm.transact().map_err(|e| {
+        anyhow::anyhow!("Failed to execute transaction {:?}: {}", hash, e)
+    })?;
+    evm.db_mut().commit(res.state.clone());
+
+    let context = evm.into_context();
+    Ok(Replay {
+        tx,
+        outcome: SimOutcome::new(res.result, res.state),
+        inspector: context.external,
+        fork_db: context.evm.inner.db,
+        block,
+    })
+}
+
+/// Replays a mined transaction like [replay_transaction] and returns the requested Parity style
+/// traces, the output of `trace_replayTransaction`
+pub async fn trace_replay_transaction(
+    client: Arc<RootProvider<PubSubFrontend>>,
+    hash: TxHash,
+    trace_types: &[TraceType]
+) -> Result<TraceResults, anyhow::Error> {
+    let (tx, _, evm) = evm_before_transaction(client, hash).await?;
+
+    let mut evm = evm
+        .modify()
+        .reset_handler_with_external_context(ParityTracer::new(trace_types))
+        .append_handler_register(inspector_handle_register)
+        .build();
+
+    *evm.tx_mut() = tx_env_from_rpc(&tx);
+    let res = evm.transact().map_err(|e| {
+        anyhow::anyhow!("Failed to execute transaction {:?}: {}", hash, e)
+    })?;
+
+    // the state diff is read before the changes are committed
+    let state_diff = match trace_types.contains(&TraceType::StateDiff) {
+        true => Some(StateDiff::new(&res.state, evm.db_mut())?),
+        false => None,
+    };
+
+    let output = res.result.output().cloned().unwrap_or_default();
+    let mut results = evm.into_context().external.into_results(output, state_diff);
+    results.transaction_hash = Some(hash);
+    Ok(results)
+}
+
+/// A field of a receipt that differs from the re-executed transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiptMismatch {
+    Status {
+        expected: bool,
+        got: bool,
+    },
+
+    GasUsed {
+        expected: u64,
+        got: u64,
+    },
+
+    Logs {
+        expected: Vec<Log>,
+        got: Vec<Log>,
+    },
+
+    /// The transaction was included by the node but failed validation on the fork
+    Rejected(String),
+}
+
+/// A transaction of a re-executed block checked against its receipt
+#[derive(Debug, Clone)]
+pub struct TxVerification {
+    pub hash: TxHash,
+
+    /// Position of the transaction in the block
+    pub index: usize,
+
+    /// `None` if the transaction was rejected
+    pub outcome: Option<SimOutcome>,
+
+    pub mismatches: Vec<ReceiptMismatch>,
+}
+
+impl TxVerification {
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// A historical block executed again on a fork of its parent
+pub struct BlockReplay {
+    pub block: Block,
+
+    pub transactions: Vec<TxVerification>,
+
+    /// Total gas used by the re-executed transactions
+    pub gas_used: u64,
+
+    /// State after the block, withdrawals included
+    pub fork_db: ForkDB,
+}
+
+impl BlockReplay {
+    /// True if every transaction matches its receipt and the gas used matches the block header
+    pub fn is_valid(&self) -> bool {
+        self.gas_used == self.block.header.gas_used as u64 &&
+            self.transactions.iter().all(|tx| tx.is_valid())
+    }
+
+    /// Returns the transactions that do not match their receipt
+    pub fn mismatches(&self) -> impl Iterator<Item = &TxVerification> {
+        self.transactions.iter().filter(|tx| !tx.is_valid())
+    }
+}
+
+/// Re-executes a whole historical block on a fork of its parent block
+///
+/// Applies the EIP-4788 beacon root before the transactions and the withdrawals after them,
+/// then compares the status, gas used and logs of each transaction against the node's receipts.
+/// Useful as a conformance check of the fork setup against the real chain
+///
+/// Block rewards of pre-merge blocks are not applied
+pub async fn replay_block(
+    client: Arc<RootProvider<PubSubFrontend>>,
+    block_number: u64
+) -> Result<BlockReplay, anyhow::Error> {
+    let block = fetch_block(&client, block_number).await?;
+    let receipts = client
+        .get_block_receipts(BlockNumberOrTag::Number(block_number)).await?
+        .ok_or_else(|| anyhow::anyhow!("Receipts of block {} not found", block_number))?;
+
+    let chain_id = client.get_chain_id().await?;
+    let mut evm = block_evm(client, &block, chain_id)?;
+
+    let transactions = block_transactions(&block)?;
+    if transactions.len() != receipts.len() {
+        return Err(
+            anyhow::anyhow!(
+                "Block {} has {} transactions but {} receipts",
+                block_number,
+                transactions.len(),
+                receipts.len()
+            )
+        );
+    }
+
+    let mut verifications = Vec::new();
+    let mut gas_used = 0;
+
+    for (index, (tx, receipt)) in transactions.iter().zip(&receipts).enumerate() {
+        *evm.tx_mut() = tx_env_from_rpc(tx);
+
+        let (outcome, mismatches) = match evm.transact() {
+            Ok(res) => {
+                evm.db_mut().commit(res.state.clone());
+                let outcome = SimOutcome::new(res.result, res.state);
+
+                let mut mismatches = Vec::new();
+                if outcome.is_success() != receipt.status() {
+                    mismatches.push(ReceiptMismatch::Status {
+                        expected: receipt.status(),
+                        got: outcome.is_success(),
+                    });
+                }
+
+                if outcome.gas_used != (receipt.gas_used as u64) {
+                    mismatches.push(ReceiptMismatch::GasUsed {
+                        expected: receipt.gas_used as u64,
+                        got: outcome.gas_used,
+                    });
+                }
+
+                let expected_logs: Vec<Log> = receipt.inner
+                    .logs()
+                    .iter()
+                    .map(|log| log.inner.clone())
+                    .collect();
+                if outcome.logs != expected_logs {
+                    mismatches.push(ReceiptMismatch::Logs {
+                        expected: expected_logs,
+                        got: outcome.logs.clone(),
+                    });
+                }
+
+                gas_used += outcome.gas_used;
+                (Some(outcome), mismatches)
+            }
+            Err(e) => (None, vec![ReceiptMismatch::Rejected(e.to_string())]),
+        };
+
+        verifications.push(TxVerification {
+            hash: tx.hash,
+            index,
+            outcome,
+            mismatches,
+        });
+    }
+
+    apply_withdrawals(evm.db_mut(), &block)?;
+
+    let fork_db = evm.into_context().evm.inner.db;
+    Ok(BlockReplay {
+        block,
+        transactions: verifications,
+        gas_used,
+        fork_db,
+    })
+}
+
+/// Returns the [SpecId] that was active on Ethereum mainnet at the given block
+///
+/// Blocks after Cancun use [SpecId::CANCUN], the latest hardfork fully supported by this revm version
+pub fn mainnet_spec_id(number: u64, timestamp: u64) -> SpecId {
+    if timestamp >= 1710338135 {
+        SpecId::CANCUN
+    } else if timestamp >= 1681338455 {
+        SpecId::SHANGHAI
+    } else if number >= 15537394 {
+        SpecId::MERGE
+    } else if number >= 15050000 {
+        SpecId::GRAY_GLACIER
+    } else if number >= 13773000 {
+        SpecId::ARROW_GLACIER
+    } else if number >= 12965000 {
+        SpecId::LONDON
+    } else if number >= 12244000 {
+        SpecId::BERLIN
+    } else if number >= 9200000 {
+        SpecId::MUIR_GLACIER
+    } else if number >= 9069000 {
+        SpecId::ISTANBUL
+    } else if number >= 7280000 {
+        SpecId::PETERSBURG
+    } else if number >= 4370000 {
+        SpecId::BYZANTIUM
+    } else if number >= 2675000 {
+        SpecId::SPURIOUS_DRAGON
+    } else if number >= 2463000 {
+        SpecId::TANGERINE
+    } else if number >= 1920000 {
+        SpecId::DAO_FORK
+    } else if number >= 1150000 {
+        SpecId::HOMESTEAD
+    } else {
+        SpecId::FRONTIER
+    }
+}
+
+// Evm with the state right before the transaction, the preceding transactions of its block are committed
+async fn evm_before_transaction(
+    client: Arc<RootProvider<PubSubFrontend>>,
+    hash: TxHash
+) -> Result<(Transaction, Block, Evm<'static, (), ForkDB>), anyhow::Error> {
+    let tx = client
+        .get_transaction_by_hash(hash).await?
+        .ok_or_else(|| anyhow::anyhow!("Transaction {:?} not found", hash))?;
+
+    let block_number = tx.block_number.ok_or_else(|| {
+        anyhow::anyhow!("Transaction {:?} is still pending", hash)
+    })?;
+
+    let block = fetch_block(&client, block_number).await?;
+    let chain_id = client.get_chain_id().await?;
+    let mut evm = block_evm(client, &block, chain_id)?;
+
+    // Reproduce the state at the position of the transaction in the block
+    for preceding in block_transactions(&block)?.iter().take_while(|t| t.hash != hash) {
+        *evm.tx_mut() = tx_env_from_rpc(preceding);
+        evm.transact_commit().map_err(|e| {
+            anyhow::anyhow!("Failed to execute preceding transaction {:?}: {}", preceding.hash, e)
+        })?;
+    }
+
+    Ok((tx, block, evm))
+}
+
+async fn fetch_block(
+    client: &Arc<RootProvider<PubSubFrontend>>,
+    number: u64
+) -> Result<Block, anyhow::Error> {
+    client
+        .get_block(BlockId::Number(BlockNumberOrTag::Number(number)), true.into()).await?
+        .ok_or_else(|| anyhow::anyhow!("Block {} not found", number))
+}
+
+fn block_transactions(block: &Block) -> Result<&[Transaction], anyhow::Error> {
+    block.transactions.as_transactions().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Block {} is missing the full transactions",
+            block.header.number.unwrap_or_default()
+        )
+    })
+}
+
+// Evm forked at the parent of the block, configured with the environment of the block
+// and the pre-block system changes applied
+fn block_evm(
+    client: Arc<RootProvider<PubSubFrontend>>,
+    block: &Block,
+    chain_id: u64
+) -> Result<Evm<'static, (), ForkDB>, anyhow::Error> {
+    let number = block.header.number.unwrap_or_default();
+    let parent = BlockId::Number(BlockNumberOrTag::Number(number.saturating_sub(1)));
+
+    let fork_factory = ForkFactory::new_sandbox_factory(
+        client,
+        CacheDB::new(EmptyDB::default()),
+        Some(parent)
+    );
+
+    let evm = new_evm_with_mode(fork_factory.new_sandbox_fork(), block.clone(), EvmMode::Strict);
+
+    let spec_id = if chain_id == 1 {
+        mainnet_spec_id(number, block.header.timestamp)
+    } else {
+        SpecId::CANCUN
+    };
+
+    let mut evm = evm.modify().with_spec_id(spec_id).build();
+    evm.cfg_mut().chain_id = chain_id;
+
+    if SpecId::enabled(spec_id, SpecId::CANCUN) {
+        apply_beacon_root(evm.db_mut(), block)?;
+    }
+    Ok(evm)
+}
+
+// EIP-4788, stores the parent beacon block root in the ring buffer of the beacon roots contract
+// the same storage writes as the system call made by the node at the start of the block
+fn apply_beacon_root(db: &mut ForkDB, block: &Block) -> Result<(), DatabaseError> {
+    const HISTORY_BUFFER_LENGTH: u64 = 8191;
+
+    let Some(root) = block.header.parent_beacon_block_root else {
+        return Ok(());
+    };
+
+    let timestamp = block.header.timestamp;
+    let timestamp_slot = U256::from(timestamp % HISTORY_BUFFER_LENGTH);
+    let root_slot = timestamp_slot + U256::from(HISTORY_BUFFER_LENGTH);
+
+    db.insert_account_storage(BEACON_ROOTS_ADDRESS, timestamp_slot, U256::from(timestamp))?;
+    db.insert_account_storage(BEACON_ROOTS_ADDRESS, root_slot, root.into())?;
+    Ok(())
+}
+
+// EIP-4895, credits the withdrawals after the transactions of the block
+fn apply_withdrawals(db: &mut ForkDB, block: &Block) -> Result<(), DatabaseError> {
+    for withdrawal in block.withdrawals.iter().flatten() {
+        let mut info = db.basic(withdrawal.address)?.unwrap_or_default();
+        info.balance += withdrawal.amount_wei();
+        db.db.insert_account_info(withdrawal.address, info);
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+pub mod node;
+
+pub use node::*;
+
+use serde_json::{ json, Value };
+use tokio::io::{ AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader };
+use tokio::net::{ TcpListener, TcpStream, ToSocketAddrs };
+use tokio::task::JoinHandle;
+
+use std::net::SocketAddr;
+use std::sync::{ Arc, Mutex };
+
+
+// larger requests are rejected before their body is read
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Serves a [ForkNode] over HTTP JSON-RPC, anvil style
+///
+/// Bind to `127.0.0.1` so the fork is only reachable from the local machine
+pub async fn serve(node: ForkNode, addr: impl ToSocketAddrs) -> Result<(), anyhow::Error> {
+    let listener = TcpListener::bind(addr).await?;
+    run(listener, Arc::new(Mutex::new(node))).await;
+    Ok(())
+}
+
+/// Runs the server in the background and returns the address it is listening on
+///
+/// Bind to `127.0.0.1:0` to get a free port
+pub async fn spawn(
+    node: ForkNode,
+    addr: impl ToSocketAddrs
+) -> Result<(SocketAddr, JoinHandle<()>), anyhow::Error> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let handle = tokio::spawn(run(listener, Arc::new(Mutex::new(node))));
+    Ok((local_addr, handle))
+}
+
+async fn run(listener: TcpListener, node: Arc<Mutex<ForkNode>>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+
+        let node = node.clone();
+        tokio::spawn(async move {
+            // a broken connection only affects its own client
+            let _ = handle_connection(stream, node).await;
+        });
+    }
+}
+
+// A minimal HTTP/1.1 server, enough for JSON-RPC clients
+struct HttpRequest {
+    method: String,
+    body: Vec<u8>,
+    keep_alive: bool,
+}
+
+async fn handle_connection(stream: TcpStream, node: Arc<Mutex<ForkNode>>) -> Result<(), anyhow::Error> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    while let Some(request) = read_request(&mut reader).await? {
+        let (status, body) = match request.method.as_str() {
+            "POST" => {
+                let response = match serde_json::from_slice::<Value>(&request.body) {
+                    Ok(body) => {
+                        match node.lock() {
+                            Ok(mut node) => node.handle_request(body),
+                            Err(_) => {
+                                let error = RpcError::server("the node is unavailable after a panic");
+                                json!({ "jsonrpc": "2.0", "id": Value::Null, "error": error })
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let error = RpcError::parse_error(e.to_string());
+                        json!({ "jsonrpc": "2.0", "id": Value::Null, "error": error })
+                    }
+                };
+                ("200 OK", Some(response.to_string()))
+            }
+            // CORS preflight of browser wallets
+            "OPTIONS" => ("204 No Content", None),
+            _ => ("405 Method Not Allowed", None),
+        };
+
+        write_response(&mut writer, status, body).await?;
+        if !request.keep_alive {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn read_request<R: AsyncBufReadExt + Unpin>(
+    reader: &mut R
+) -> Result<Option<HttpRequest>, anyhow::Error> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let version = parts.nth(1).unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut keep_alive = version == "HTTP/1.1";
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = value.parse()?;
+            }
+            "connection" => {
+                keep_alive = value.eq_ignore_ascii_case("keep-alive");
+            }
+            _ => {}
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err(anyhow::anyhow!("Request body of {} bytes exceeds {} bytes", content_length, MAX_BODY_SIZE));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(HttpRequest { method, body, keep_alive }))
+}
+
+async fn write_response<W: AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    status: &str,
+    body: Option<String>
+) -> Result<(), anyhow::Error> {
+    let body = body.unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 {}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        Access-Control-Allow-Origin: *\r\n\
+        Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+        Access-Control-Allow-Headers: Content-Type\r\n\
+        \r\n\
+        {}",
+        status,
+        body.len(),
+        body
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forked_db::fork_db::ForkDB;
+    use crate::tx_env::sign_raw_tx;
+    use alloy::consensus::TxEip1559;
+    use alloy::primitives::{ address, bytes, Address, Bytes, TxKind, B256, U256 };
+    use alloy::rpc::types::eth::Block;
+    use alloy::signers::local::PrivateKeySigner;
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode };
+
+    const CONTRACT: Address = address!("2222222222222222222222222222222222222222");
+    const RECIPIENT: Address = address!("3333333333333333333333333333333333333333");
+
+    // returns 42
+    const CODE: Bytes = bytes!("602a60005260206000f3");
+
+    fn signer() -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&B256::repeat_byte(0x42)).unwrap()
+    }
+
+    async fn spawn_offline() -> SocketAddr {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CONTRACT, AccountInfo {
+            code: Some(Bytecode::new_raw(CODE)),
+            ..Default::default()
+        });
+        db.insert_account_storage(CONTRACT, U256::ZERO, U256::from(7)).unwrap();
+        db.insert_account_info(signer().address(), AccountInfo {
+            balance: U256::from(10).pow(U256::from(18)),
+            ..Default::default()
+        });
+
+        let mut block = Block::default();
+        block.header.number = Some(1);
+        block.header.gas_limit = 30_000_000;
+        block.header.base_fee_per_gas = Some(1_000_000_000);
+        let node = ForkNode::new(ForkDB::offline(db), block, 1);
+        let (addr, _) = spawn(node, "127.0.0.1:0").await.unwrap();
+        addr
+    }
+
+    // Sends a raw HTTP request and returns the raw response, empty if the connection was closed
+    async fn send(addr: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request).await.unwrap();
+
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        response
+    }
+
+    async fn rpc(addr: SocketAddr, method: &str, params: Value) -> Value {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string();
+        let request = format!(
+            "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let response = send(addr, request.as_bytes()).await;
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let response: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(response["error"], Value::Null, "{} failed", method);
+        response["result"].clone()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn round_trip() {
+        let addr = spawn_offline().await;
+
+        assert_eq!(rpc(addr, "eth_chainId", json!([])).await, "0x1");
+
+        let call = json!({ "to": CONTRACT, "data": Bytes::new() });
+        let result = rpc(addr, "eth_call", json!([call, "latest"])).await;
+        assert_eq!(result, json!(B256::with_last_byte(42)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn send_mine_and_query() {
+        let addr = spawn_offline().await;
+        assert_eq!(rpc(addr, "eth_blockNumber", json!([])).await, "0x1");
+        rpc(addr, "evm_setAutomine", json!([false])).await;
+
+        let tx = TxEip1559 {
+            chain_id: 1,
+            gas_limit: 21_000,
+            max_fee_per_gas: 2_000_000_000,
+            max_priority_fee_per_gas: 1_000_000_000,
+            to: TxKind::Call(RECIPIENT),
+            value: U256::from(1000),
+            ..Default::default()
+        };
+        let raw = sign_raw_tx(&signer(), tx);
+        let hash = rpc(addr, "eth_sendRawTransaction", json!([raw])).await;
+        assert_eq!(rpc(addr, "eth_getTransactionReceipt", json!([hash])).await, Value::Null);
+
+        rpc(addr, "evm_mine", json!([])).await;
+        let receipt = rpc(addr, "eth_getTransactionReceipt", json!([hash])).await;
+        assert_eq!(receipt["status"], "0x1");
+        assert_eq!(receipt["blockNumber"], "0x2");
+        assert_eq!(receipt["gasUsed"], "0x5208");
+        assert_eq!(receipt["from"], json!(signer().address()));
+        assert_eq!(rpc(addr, "eth_blockNumber", json!([])).await, "0x2");
+
+        let balance = rpc(addr, "eth_getBalance", json!([RECIPIENT, "latest"])).await;
+        assert_eq!(balance, json!(U256::from(1000)));
+        let nonce = rpc(addr, "eth_getTransactionCount", json!([signer().address(), "latest"])).await;
+        assert_eq!(nonce, "0x1");
+
+        let value = rpc(addr, "eth_getStorageAt", json!([CONTRACT, "0x0", "latest"])).await;
+        assert_eq!(value, json!(B256::with_last_byte(7)));
+        assert_eq!(rpc(addr, "eth_getCode", json!([CONTRACT, "latest"])).await, json!(CODE));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn oversized_body() {
+        let addr = spawn_offline().await;
+
+        let request = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+        // closed without reading the body, which is never sent
+        let response = tokio::time::timeout(std::time::Duration::from_secs(5), send(addr, request.as_bytes())).await;
+        assert_eq!(response.unwrap(), "");
+
+        // the server keeps serving other clients
+        assert_eq!(rpc(addr, "eth_chainId", json!([])).await, "0x1");
+    }
+}
@@ -0,0 +1,649 @@
+use alloy::consensus::{ Receipt, ReceiptEnvelope, ReceiptWithBloom };
+use alloy::primitives::{ keccak256, Address, Bloom, Bytes, B256, U256, U64 };
+use alloy::rpc::types::eth::{
+    Block,
+    BlockId,
+    BlockNumberOrTag,
+    BlockTransactions,
+    Log as RpcLog,
+    TransactionReceipt,
+    TransactionRequest,
+};
+use hashbrown::HashMap;
+use revm::primitives::TxEnv;
+use revm::Database;
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+use serde_json::{ json, Value };
+
+use std::panic::{ self, AssertUnwindSafe };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use crate::forked_db::fork_db::ForkDB;
+use crate::inspectors::{ CallTracerConfig, StructLoggerConfig, TraceType };
+use crate::simulator::{ EstimateGasError, SimOutcome, SimStatus, Simulator };
+use crate::tx_env::{ decode_raw_tx, tx_env_from_request };
+use crate::EvmMode;
+
+
+/// A JSON-RPC error object
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct RpcError {
+    pub code: i64,
+
+    pub message: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn parse_error(message: impl Into<String>) -> Self {
+        Self::new(-32700, message)
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(-32600, message)
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(-32601, format!("the method {} does not exist/is not available", method))
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(-32602, message)
+    }
+
+    /// Server error, eg. a rejected transaction
+    pub fn server(message: impl Into<String>) -> Self {
+        Self::new(-32000, message)
+    }
+
+    /// Reverted call, the revert data is returned in `data` like geth does
+    pub fn reverted(reason: &str, output: &Bytes) -> Self {
+        let message = if output.is_empty() {
+            "execution reverted".to_string()
+        } else {
+            format!("execution reverted: {}", reason)
+        };
+
+        Self {
+            code: 3,
+            message,
+            data: Some(json!(output)),
+        }
+    }
+}
+
+// The latest block of the local chain
+#[derive(Debug, Clone)]
+struct LocalBlock {
+    number: u64,
+    hash: B256,
+    timestamp: u64,
+}
+
+// A transaction executed on the pending block, waiting to be mined
+struct PendingTx {
+    hash: B256,
+    from: Address,
+    to: Option<Address>,
+    tx_type: u8,
+    effective_gas_price: u128,
+    outcome: SimOutcome,
+}
+
+// The options of `debug_traceCall`, the flags of the default tracer are at the top level like in geth
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceOptions {
+    tracer: Option<String>,
+
+    tracer_config: Option<CallTracerConfig>,
+
+    #[serde(flatten)]
+    logger: StructLoggerConfig,
+}
+
+/// A local node on top of a fork, answers JSON-RPC requests like anvil
+///
+/// Transactions are executed on the pending block as soon as they are received
+/// and mined into a new local block, immediately if [ForkNode::automine] is on or with `evm_mine`
+///
+/// Only the latest state can be queried, older block numbers are rejected
+pub struct ForkNode {
+    /// `None` only while a request is executed
+    fork_db: Option<ForkDB>,
+
+    /// The fork block with the number and timestamp of the pending block
+    block: Block,
+
+    mode: EvmMode,
+
+    chain_id: u64,
+
+    /// Gas limit of calls that don't set one
+    gas_limit: u64,
+
+    base_fee: u128,
+
+    head: LocalBlock,
+
+    pending: Vec<PendingTx>,
+
+    receipts: HashMap<B256, TransactionReceipt>,
+
+    /// Mine a block after every transaction, on by default
+    pub automine: bool,
+}
+
+impl ForkNode {
+    /// Creates a node on top of `block` with the [EvmMode::Relaxed] checks
+    pub fn new(fork_db: ForkDB, block: Block, chain_id: u64) -> Self {
+        Self::with_mode(fork_db, block, chain_id, EvmMode::Relaxed)
+    }
+
+    pub fn with_mode(fork_db: ForkDB, mut block: Block, chain_id: u64, mode: EvmMode) -> Self {
+        let head = LocalBlock {
+            number: block.header.number.unwrap_or_default(),
+            hash: block.header.hash.unwrap_or_default(),
+            timestamp: block.header.timestamp,
+        };
+        let gas_limit = block.header.gas_limit as u64;
+        let base_fee = block.header.base_fee_per_gas.unwrap_or_default();
+
+        // only the header is needed
+        block.transactions = BlockTransactions::default();
+
+        let mut node = Self {
+            fork_db: Some(fork_db),
+            block,
+            mode,
+            chain_id,
+            gas_limit,
+            base_fee,
+            head,
+            pending: Vec::new(),
+            receipts: HashMap::new(),
+            automine: true,
+        };
+        node.open_pending_block();
+        node
+    }
+
+    /// Number of the latest mined block
+    pub fn block_number(&self) -> u64 {
+        self.head.number
+    }
+
+    /// Mines the pending transactions into a new block, returns the block number
+    pub fn mine(&mut self) -> u64 {
+        let number = self.head.number + 1;
+        let timestamp = self.block.header.timestamp;
+
+        let mut preimage = self.head.hash.to_vec();
+        preimage.extend_from_slice(&number.to_be_bytes());
+        for tx in &self.pending {
+            preimage.extend_from_slice(tx.hash.as_slice());
+        }
+        let hash = keccak256(preimage);
+
+        let mut cumulative_gas_used = 0;
+        let mut log_index = 0;
+
+        for (index, tx) in self.pending.drain(..).enumerate() {
+            cumulative_gas_used += tx.outcome.gas_used;
+
+            let logs = tx.outcome.logs
+                .iter()
+                .map(|log| {
+                    log_index += 1;
+                    RpcLog {
+                        inner: log.clone(),
+                        block_hash: Some(hash),
+                        block_number: Some(number),
+                        block_timestamp: Some(timestamp),
+                        transaction_hash: Some(tx.hash),
+                        transaction_index: Some(index as u64),
+                        log_index: Some(log_index - 1),
+                        removed: false,
+                    }
+                })
+                .collect();
+
+            let bloom: Bloom = tx.outcome.logs.iter().collect();
+            let receipt = Receipt {
+                status: tx.outcome.is_success().into(),
+                cumulative_gas_used: cumulative_gas_used as u128,
+                logs,
+            };
+            let receipt = ReceiptWithBloom::new(receipt, bloom);
+
+            let inner = match tx.tx_type {
+                1 => ReceiptEnvelope::Eip2930(receipt),
+                2 => ReceiptEnvelope::Eip1559(receipt),
+                3 => ReceiptEnvelope::Eip4844(receipt),
+                _ => ReceiptEnvelope::Legacy(receipt),
+            };
+
+            self.receipts.insert(tx.hash, TransactionReceipt {
+                inner,
+                transaction_hash: tx.hash,
+                transaction_index: Some(index as u64),
+                block_hash: Some(hash),
+                block_number: Some(number),
+                gas_used: tx.outcome.gas_used as u128,
+                effective_gas_price: tx.effective_gas_price,
+                blob_gas_used: None,
+                blob_gas_price: None,
+                from: tx.from,
+                to: tx.to,
+                contract_address: tx.outcome.created_address,
+                state_root: None,
+            });
+        }
+
+        self.head = LocalBlock { number, hash, timestamp };
+        self.open_pending_block();
+        number
+    }
+
+    /// Handles a JSON-RPC request object or a batch of them, returns the response
+    pub fn handle_request(&mut self, request: Value) -> Value {
+        match request {
+            Value::Array(batch) => {
+                Value::Array(batch.into_iter().map(|r| self.handle_single(r)).collect())
+            }
+            request => self.handle_single(request),
+        }
+    }
+
+    /// Executes a JSON-RPC method
+    pub fn handle(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "eth_chainId" => to_value(U64::from(self.chain_id)),
+            "net_version" => to_value(self.chain_id.to_string()),
+            "eth_blockNumber" => to_value(U64::from(self.head.number)),
+            "eth_gasPrice" => to_value(U256::from(self.base_fee)),
+            "eth_maxPriorityFeePerGas" => to_value(U256::from(1_000_000_000u64)),
+            "eth_feeHistory" => {
+                let block_count: U64 = param(params, 0)?;
+                let block_count = block_count.to::<u64>().clamp(1, 1024);
+                let percentiles: Option<Vec<f64>> = param(params, 2)?;
+
+                let oldest_block = (self.head.number + 1).saturating_sub(block_count);
+                let rewards = percentiles.map(|p| vec![vec![U256::ZERO; p.len()]; block_count as usize]);
+
+                to_value(json!({
+                    "oldestBlock": U64::from(oldest_block),
+                    "baseFeePerGas": vec![U256::from(self.base_fee); block_count as usize + 1],
+                    "gasUsedRatio": vec![0.0; block_count as usize],
+                    "reward": rewards,
+                }))
+            }
+            "eth_getBlockByNumber" => {
+                // the local blocks don't keep their transactions
+                let full: Option<bool> = param(params, 1)?;
+                if full == Some(true) {
+                    return Err(RpcError::invalid_params("blocks with full transactions are not supported"));
+                }
+                let block = self.check_block(params, 0).ok().map(|_| self.latest_block());
+                to_value(block)
+            }
+            "eth_getBalance" => {
+                let address: Address = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let info = self.db().basic(address).map_err(server_error)?;
+                to_value(info.map(|i| i.balance).unwrap_or_default())
+            }
+            "eth_getTransactionCount" => {
+                let address: Address = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let info = self.db().basic(address).map_err(server_error)?;
+                to_value(U64::from(info.map(|i| i.nonce).unwrap_or_default()))
+            }
+            "eth_getCode" => {
+                let address: Address = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let db = self.db();
+                let code = match db.basic(address).map_err(server_error)? {
+                    Some(info) => {
+                        match info.code {
+                            Some(code) => code.original_bytes(),
+                            None => db.code_by_hash(info.code_hash).map_err(server_error)?.original_bytes(),
+                        }
+                    }
+                    None => Bytes::new(),
+                };
+                to_value(code)
+            }
+            "eth_getStorageAt" => {
+                let address: Address = param(params, 0)?;
+                let slot: U256 = param(params, 1)?;
+                self.check_block(params, 2)?;
+                let value = self.db().storage(address, slot).map_err(server_error)?;
+                to_value(B256::from(value))
+            }
+            "eth_call" => {
+                let request: TransactionRequest = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let outcome = self.call(&request)?;
+                to_value(outcome.output)
+            }
+            "eth_estimateGas" => {
+                let request: TransactionRequest = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let tx = tx_env_from_request(&request, self.gas_limit);
+                let gas = self.with_call_simulator(tx, |simulator, tx| simulator.estimate_gas(tx)).map_err(|e| {
+                    match e.downcast_ref::<EstimateGasError>() {
+                        Some(EstimateGasError::Reverted { reason, output }) => {
+                            RpcError::reverted(reason, output)
+                        }
+                        _ => server_error(e),
+                    }
+                })?;
+                to_value(U64::from(gas))
+            }
+            "eth_createAccessList" => {
+                let request: TransactionRequest = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let tx = tx_env_from_request(&request, self.gas_limit);
+                let report = self
+                    .with_call_simulator(tx, |simulator, tx| simulator.create_access_list(tx))
+                    .map_err(server_error)?;
+
+                let error = match report.outcome.status {
+                    SimStatus::Success => None,
+                    SimStatus::Revert(reason) => Some(format!("execution reverted: {}", reason)),
+                    SimStatus::Halt(reason) => Some(format!("{:?}", reason)),
+                };
+                to_value(json!({
+                    "accessList": report.access_list,
+                    "gasUsed": U64::from(report.outcome.gas_used),
+                    "error": error,
+                }))
+            }
+            "trace_call" => {
+                let request: TransactionRequest = param(params, 0)?;
+                let trace_types: Vec<TraceType> = param(params, 1)?;
+                self.check_block(params, 2)?;
+                let tx = tx_env_from_request(&request, self.gas_limit);
+                let results = self
+                    .with_call_simulator(tx, |simulator, tx| simulator.trace_call(tx, &trace_types))
+                    .map_err(server_error)?;
+                to_value(results)
+            }
+            "debug_traceCall" => {
+                let request: TransactionRequest = param(params, 0)?;
+                self.check_block(params, 1)?;
+                let options: TraceOptions = param::<Option<TraceOptions>>(params, 2)?.unwrap_or_default();
+                let tx = tx_env_from_request(&request, self.gas_limit);
+
+                match options.tracer.as_deref() {
+                    None => {
+                        let result = self
+                            .with_call_simulator(tx, |simulator, tx| simulator.struct_logs(tx, options.logger))
+                            .map_err(server_error)?;
+                        to_value(result)
+                    }
+                    Some("callTracer") => {
+                        let config = options.tracer_config.unwrap_or_default();
+                        let (_, frame) = self
+                            .with_call_simulator(tx, |simulator, tx| simulator.call_trace(tx, config))
+                            .map_err(server_error)?;
+                        to_value(frame)
+                    }
+                    Some(tracer) => Err(RpcError::invalid_params(format!("unsupported tracer {}", tracer))),
+                }
+            }
+            "eth_sendRawTransaction" => {
+                let raw: Bytes = param(params, 0)?;
+                to_value(self.send_raw_transaction(&raw)?)
+            }
+            "eth_getTransactionReceipt" => {
+                let hash: B256 = param(params, 0)?;
+                to_value(self.receipts.get(&hash))
+            }
+            "evm_mine" => {
+                self.mine();
+                to_value("0x0")
+            }
+            "evm_setAutomine" => {
+                self.automine = param(params, 0)?;
+                if self.automine && !self.pending.is_empty() {
+                    self.mine();
+                }
+                to_value(true)
+            }
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    fn handle_single(&mut self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some(method) => {
+                let params = request.get("params").cloned().unwrap_or(Value::Null);
+                // a panic must not take down the node, the fork db is restored by `with_simulator`
+                panic::catch_unwind(AssertUnwindSafe(|| self.handle(method, &params))).unwrap_or_else(|payload| {
+                    Err(RpcError::server(format!("internal error: {}", panic_message(&*payload))))
+                })
+            }
+            None => Err(RpcError::invalid_request("missing method")),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        }
+    }
+
+    // Executes a call on the pending block, reverts and halts are returned as errors
+    fn call(&mut self, request: &TransactionRequest) -> Result<SimOutcome, RpcError> {
+        let tx = tx_env_from_request(request, self.gas_limit);
+        let outcome = self.with_call_simulator(tx, |simulator, tx| simulator.call_env(tx)).map_err(server_error)?;
+
+        match &outcome.status {
+            SimStatus::Success => Ok(outcome),
+            SimStatus::Revert(reason) => Err(RpcError::reverted(reason, &outcome.output)),
+            SimStatus::Halt(reason) => Err(RpcError::server(format!("{:?}", reason))),
+        }
+    }
+
+    fn send_raw_transaction(&mut self, raw: &[u8]) -> Result<B256, RpcError> {
+        let decoded = decode_raw_tx(raw).map_err(|e| RpcError::invalid_params(e.to_string()))?;
+
+        if decoded.tx_env.chain_id.is_some_and(|id| id != self.chain_id) {
+            return Err(RpcError::server("invalid chain id"));
+        }
+
+        // the base fee is paid like on chain, also in relaxed mode
+        let (effective_gas_price, outcome) = self.with_simulator(|simulator| {
+            simulator.with_header_basefee(|simulator| {
+                *simulator.evm.tx_mut() = decoded.tx_env.clone();
+                let effective_gas_price = simulator.evm.context.evm.env.effective_gas_price();
+                (effective_gas_price, simulator.transact_env(decoded.tx_env.clone()))
+            })
+        });
+        let outcome = outcome.map_err(server_error)?;
+
+        self.pending.push(PendingTx {
+            hash: decoded.hash,
+            from: decoded.from,
+            to: decoded.tx_env.transact_to.to().copied(),
+            tx_type: decoded.envelope.tx_type() as u8,
+            effective_gas_price: effective_gas_price.to::<u128>(),
+            outcome,
+        });
+
+        if self.automine {
+            self.mine();
+        }
+        Ok(decoded.hash)
+    }
+
+    // Only the state of the latest block is available
+    fn check_block(&self, params: &Value, index: usize) -> Result<(), RpcError> {
+        let block = match params.get(index) {
+            None | Some(Value::Null) => {
+                return Ok(());
+            }
+            Some(block) => {
+                serde_json::from_value::<BlockId>(block.clone()).map_err(|e| {
+                    RpcError::invalid_params(e.to_string())
+                })?
+            }
+        };
+
+        match block {
+            BlockId::Number(BlockNumberOrTag::Latest | BlockNumberOrTag::Pending) => Ok(()),
+            BlockId::Number(BlockNumberOrTag::Number(number)) if number == self.head.number => Ok(()),
+            BlockId::Hash(hash) if hash.block_hash == self.head.hash => Ok(()),
+            _ => Err(RpcError::invalid_params("only the latest block is supported")),
+        }
+    }
+
+    // The pending block is where new transactions and calls are executed
+    fn open_pending_block(&mut self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let timestamp = now.max(self.head.timestamp + 1);
+
+        self.block.header.number = Some(self.head.number + 1);
+        self.block.header.timestamp = timestamp;
+    }
+
+    // Header of the latest mined block, transactions are not included
+    fn latest_block(&self) -> Block {
+        let mut block = self.block.clone();
+        block.header.number = Some(self.head.number);
+        block.header.hash = Some(self.head.hash);
+        block.header.timestamp = self.head.timestamp;
+        block
+    }
+
+    fn db(&mut self) -> &mut ForkDB {
+        self.fork_db.as_mut().expect("fork db is in use")
+    }
+
+    // The Evm is not `Send`, so it is built for each request on top of the fork state
+    fn with_simulator<T>(&mut self, f: impl FnOnce(&mut Simulator) -> T) -> T {
+        let fork_db = self.fork_db.take().expect("fork db is in use");
+
+        let mut simulator = Simulator::with_mode(fork_db, self.block.clone(), self.mode);
+        simulator.evm.cfg_mut().chain_id = self.chain_id;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut simulator)));
+
+        self.fork_db = Some(simulator.evm.into_context().evm.inner.db);
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    // Calls without a gas price skip the base fee check like in geth and anvil, also in strict mode
+    fn with_call_simulator<T>(&mut self, tx: TxEnv, f: impl FnOnce(&mut Simulator, TxEnv) -> T) -> T {
+        let free = tx.gas_price.is_zero() && tx.gas_priority_fee.unwrap_or_default().is_zero();
+        self.with_simulator(|simulator| {
+            if free {
+                simulator.evm.cfg_mut().disable_base_fee = true;
+            }
+            f(simulator, tx)
+        })
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &Value, index: usize) -> Result<T, RpcError> {
+    let value = params.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| {
+        RpcError::invalid_params(format!("invalid param {}: {}", index, e))
+    })
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::server(e.to_string()))
+}
+
+fn server_error(err: impl std::fmt::Display) -> RpcError {
+    RpcError::server(err.to_string())
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map(String::as_str).unwrap_or("panic"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tx_env::sign_raw_tx;
+    use alloy::consensus::TxEip1559;
+    use alloy::primitives::{ address, TxKind };
+    use alloy::signers::local::PrivateKeySigner;
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::AccountInfo;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn offline_node(signer: &PrivateKeySigner, mode: EvmMode) -> ForkNode {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(signer.address(), AccountInfo {
+            balance: U256::from(10).pow(U256::from(18)),
+            ..Default::default()
+        });
+
+        let mut block = Block::default();
+        block.header.number = Some(1);
+        block.header.gas_limit = 30_000_000;
+        block.header.base_fee_per_gas = Some(10 * GWEI);
+        ForkNode::with_mode(ForkDB::offline(db), block, 1, mode)
+    }
+
+    #[test]
+    fn relaxed_transactions_pay_the_base_fee() {
+        let signer = PrivateKeySigner::from_bytes(&B256::repeat_byte(0x42)).unwrap();
+        let mut node = offline_node(&signer, EvmMode::Relaxed);
+        let balance_before = node.db().basic(signer.address()).unwrap().unwrap().balance;
+
+        let tx = TxEip1559 {
+            chain_id: 1,
+            gas_limit: 21_000,
+            max_fee_per_gas: 20 * GWEI,
+            max_priority_fee_per_gas: 2 * GWEI,
+            to: TxKind::Call(address!("2222222222222222222222222222222222222222")),
+            value: U256::from(1),
+            ..Default::default()
+        };
+        let hash = node.send_raw_transaction(&sign_raw_tx(&signer, tx)).unwrap();
+
+        let receipt = node.receipts.get(&hash).unwrap();
+        assert_eq!(receipt.effective_gas_price, 12 * GWEI);
+
+        let balance_after = node.db().basic(signer.address()).unwrap().unwrap().balance;
+        assert_eq!(balance_before - balance_after, U256::from(21_000 * 12 * GWEI + 1));
+    }
+
+    #[test]
+    fn strict_calls_without_gas_price() {
+        let signer = PrivateKeySigner::from_bytes(&B256::repeat_byte(0x42)).unwrap();
+        let mut node = offline_node(&signer, EvmMode::Strict);
+        let call = json!({
+            "from": address!("3333333333333333333333333333333333333333"),
+            "to": address!("2222222222222222222222222222222222222222"),
+        });
+
+        assert_eq!(node.handle("eth_call", &json!([call, "latest"])).unwrap(), json!("0x"));
+        assert_eq!(node.handle("eth_estimateGas", &json!([call])).unwrap(), json!("0x5208"));
+
+        // a price below the base fee is still rejected
+        let call = json!({ "to": address!("2222222222222222222222222222222222222222"), "gasPrice": "0x1" });
+        assert!(node.handle("eth_call", &json!([call, "latest"])).is_err());
+    }
+}
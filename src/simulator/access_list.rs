@@ -0,0 +1,78 @@
+use alloy::eips::eip2930::AccessList;
+use revm::precompile::{ PrecompileSpecId, Precompiles };
+use revm::primitives::{ TransactTo, TxEnv };
+
+use super::{ SimOutcome, Simulator };
+use crate::deploy::create_address;
+use crate::inspectors::AccessListInspector;
+
+
+// The access list changes the gas available to the calls, so the transaction is run
+// again with the new list until it is stable
+const MAX_ACCESS_LIST_ROUNDS: usize = 10;
+
+/// An access list generated by simulating a transaction, like `eth_createAccessList`
+#[derive(Debug, Clone)]
+pub struct AccessListReport {
+    pub access_list: AccessList,
+
+    /// Outcome of the transaction with the access list
+    pub outcome: SimOutcome,
+
+    /// Gas used by the transaction without an access list
+    pub gas_used_without: u64,
+}
+
+impl AccessListReport {
+    /// Gas saved by attaching the access list, negative if the list costs more than it saves
+    pub fn gas_saved(&self) -> i64 {
+        (self.gas_used_without as i64) - (self.outcome.gas_used as i64)
+    }
+}
+
+impl Simulator {
+    /// Generates the EIP-2930 access list of a transaction without committing any state changes
+    ///
+    /// The sender, the recipient (or the created contract) and the precompiles are warm anyway
+    /// and are only listed with the storage keys they touch. Any access list already set on `tx` is replaced
+    pub fn create_access_list(&mut self, tx: TxEnv) -> Result<AccessListReport, anyhow::Error> {
+        let recipient = match tx.transact_to {
+            TransactTo::Call(to) => to,
+            TransactTo::Create => {
+                let nonce = match tx.nonce {
+                    Some(nonce) => nonce,
+                    None => self.nonce(tx.caller)?,
+                };
+                create_address(tx.caller, nonce)
+            }
+        };
+
+        let precompiles = Precompiles::new(PrecompileSpecId::from_spec_id(self.evm.spec_id()));
+        let mut excluded = vec![tx.caller, recipient];
+        excluded.extend(precompiles.addresses().copied());
+
+        let mut without_list = tx.clone();
+        without_list.access_list.clear();
+        let gas_used_without = self.call_env(without_list)?.gas_used;
+
+        let mut access_list = AccessList::default();
+        for _ in 0..MAX_ACCESS_LIST_ROUNDS {
+            let mut with_list = tx.clone();
+            with_list.access_list = access_list.0.clone();
+
+            let (outcome, inspector) = self.inspect(with_list, AccessListInspector::new(excluded.clone()))?;
+            let new_list = inspector.access_list();
+
+            if new_list == access_list {
+                return Ok(AccessListReport {
+                    access_list,
+                    outcome,
+                    gas_used_without,
+                });
+            }
+            access_list = new_list;
+        }
+
+        Err(anyhow::anyhow!("Access list did not converge after {} rounds", MAX_ACCESS_LIST_ROUNDS))
+    }
+}
@@ -0,0 +1,246 @@
+use alloy::primitives::{ Address, Log, I256 };
+use bigdecimal::BigDecimal;
+use revm::primitives::{ EvmState, TxEnv };
+use revm::Database;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use super::{ SimOutcome, Simulator };
+use crate::utils::{ decode_logs, DecodedEvent, ERC20Token };
+use crate::WETH;
+
+
+/// An asset whose balance can change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Asset {
+    Eth,
+
+    /// An ERC20 token by its address
+    Erc20(Address),
+}
+
+/// The net balance change of an asset for an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssetChange {
+    pub address: Address,
+    pub asset: Asset,
+
+    /// Negative if the address lost some of the asset
+    pub delta: I256,
+}
+
+/// The net balance changes of every address, like Tenderly's asset changes view
+///
+/// ETH changes come from the state changes so internal transfers and gas fees are included.
+/// ERC20 changes come from the `Transfer` events and the `Deposit` and `Withdrawal` events of WETH,
+/// mints and burns are not attributed to the zero address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetChanges {
+    // sorted by address then asset, zero deltas are removed
+    changes: BTreeMap<(Address, Asset), I256>,
+}
+
+impl AssetChanges {
+    /// Builds the report from the state changes and the logs of a transaction
+    ///
+    /// `db` must not have the changes committed yet, it is used to read the previous ETH balances
+    pub fn new<DB: Database>(state: &EvmState, logs: &[Log], db: &mut DB) -> Result<Self, DB::Error> {
+        let mut changes = BTreeMap::new();
+
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+            let before = db.basic(*address)?.map(|info| info.balance).unwrap_or_default();
+            let delta = I256::from_raw(account.info.balance) - I256::from_raw(before);
+            add(&mut changes, *address, Asset::Eth, delta);
+        }
+
+        for log in decode_logs(logs) {
+            let token = Asset::Erc20(log.address);
+            match log.event {
+                DecodedEvent::Transfer(transfer) => {
+                    let value = I256::from_raw(transfer.value);
+                    add(&mut changes, transfer.from, token, -value);
+                    add(&mut changes, transfer.to, token, value);
+                }
+                // other contracts, eg. vaults and bridges, emit the same events
+                DecodedEvent::Deposit(deposit) if log.address == *WETH => {
+                    add(&mut changes, deposit.dst, token, I256::from_raw(deposit.wad));
+                }
+                DecodedEvent::Withdrawal(withdrawal) if log.address == *WETH => {
+                    add(&mut changes, withdrawal.src, token, -I256::from_raw(withdrawal.wad));
+                }
+                _ => {}
+            }
+        }
+
+        changes.retain(|(address, _), delta| !address.is_zero() && !delta.is_zero());
+        Ok(Self { changes })
+    }
+
+    /// All the changes, sorted by address then asset
+    pub fn changes(&self) -> Vec<AssetChange> {
+        self.changes
+            .iter()
+            .map(|((address, asset), delta)| AssetChange {
+                address: *address,
+                asset: *asset,
+                delta: *delta,
+            })
+            .collect()
+    }
+
+    /// The net change of an asset for an address, zero if unchanged
+    pub fn delta(&self, address: Address, asset: Asset) -> I256 {
+        self.changes.get(&(address, asset)).copied().unwrap_or_default()
+    }
+
+    /// Addresses of the ERC20 tokens that moved, to fetch their metadata
+    pub fn tokens(&self) -> Vec<Address> {
+        let mut tokens: Vec<Address> = self.changes
+            .keys()
+            .filter_map(|(_, asset)| match asset {
+                Asset::Erc20(token) => Some(*token),
+                Asset::Eth => None,
+            })
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+        tokens
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Formats the changes per address, amounts of `tokens` are shown with their decimals and symbol
+    ///
+    /// Tokens missing from `tokens` are shown in their smallest unit with their address
+    pub fn to_report(&self, tokens: &[ERC20Token]) -> String {
+        let mut report = String::new();
+        let mut current = None;
+
+        for ((address, asset), delta) in &self.changes {
+            if current != Some(*address) {
+                let _ = writeln!(report, "{:?}", address);
+                current = Some(*address);
+            }
+
+            let amount = match asset {
+                Asset::Eth => format_amount(*delta, 18, "ETH"),
+                Asset::Erc20(token) => match tokens.iter().find(|t| t.address == *token) {
+                    Some(t) => format_amount(*delta, t.decimals, &t.symbol),
+                    None => format!("{} {:?}", with_sign(*delta, delta.to_string()), token),
+                },
+            };
+            let _ = writeln!(report, "    {}", amount);
+        }
+        report
+    }
+}
+
+impl Simulator {
+    /// Executes a transaction without committing the state changes and returns the net
+    /// ETH and ERC20 balance changes of every address
+    ///
+    /// The gas fee is computed with the base fee of the block header, also in [crate::EvmMode::Relaxed]
+    pub fn asset_changes(&mut self, tx: TxEnv) -> Result<(SimOutcome, AssetChanges), anyhow::Error> {
+        let outcome = self.with_header_basefee(|simulator| simulator.call_env(tx))?;
+        let changes = AssetChanges::new(&outcome.state_changes, &outcome.logs, self.evm.db_mut())?;
+        Ok((outcome, changes))
+    }
+}
+
+fn add(changes: &mut BTreeMap<(Address, Asset), I256>, address: Address, asset: Asset, delta: I256) {
+    let entry = changes.entry((address, asset)).or_default();
+    *entry += delta;
+}
+
+fn format_amount(delta: I256, decimals: u8, symbol: &str) -> String {
+    let divisor_str = format!("1{:0>width$}", "", width = decimals as usize);
+    let divisor = BigDecimal::from_str(&divisor_str).unwrap();
+    let amount = (BigDecimal::from_str(&delta.to_string()).unwrap() / divisor).with_scale(decimals as i64);
+
+    // exact amounts, gas fees are lost with a fixed precision
+    let amount = amount.to_string();
+    let amount = match amount.contains('.') {
+        true => amount.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => amount,
+    };
+    format!("{} {}", with_sign(delta, amount), symbol)
+}
+
+fn with_sign(delta: I256, amount: String) -> String {
+    match delta.is_positive() {
+        true => format!("+{}", amount),
+        false => amount,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Transfer;
+    use crate::EvmMode;
+    use alloy::primitives::{ address, U256 };
+    use alloy::sol_types::SolEvent;
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode, TransactTo };
+
+    const CALLER: Address = address!("1111111111111111111111111111111111111111");
+    const TOKEN: Address = address!("2222222222222222222222222222222222222222");
+    const RECEIVER: Address = address!("3333333333333333333333333333333333333333");
+
+    // emits Transfer(CALLER, RECEIVER, 100) to any call
+    fn token_code() -> Bytecode {
+        let mut code = vec![0x60, 0x64, 0x60, 0x00, 0x52, 0x73];
+        code.extend_from_slice(RECEIVER.as_slice());
+        code.push(0x73);
+        code.extend_from_slice(CALLER.as_slice());
+        code.push(0x7f);
+        code.extend_from_slice(Transfer::SIGNATURE_HASH.as_slice());
+        code.extend_from_slice(&[0x60, 0x20, 0x60, 0x00, 0xa3, 0x00]);
+        Bytecode::new_raw(code.into())
+    }
+
+    #[test]
+    fn eth_and_erc20_deltas() {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CALLER, AccountInfo {
+            balance: U256::from(10).pow(U256::from(18)),
+            ..Default::default()
+        });
+        db.insert_account_info(TOKEN, AccountInfo {
+            code: Some(token_code()),
+            ..Default::default()
+        });
+        let mut simulator = Simulator::offline(db, EvmMode::Strict);
+
+        let value = U256::from(1000);
+        let gas_price = U256::from(1_000_000_000);
+        let tx = TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(TOKEN),
+            value,
+            gas_limit: 100_000,
+            gas_price,
+            ..Default::default()
+        };
+        let (outcome, changes) = simulator.asset_changes(tx).unwrap();
+        assert!(outcome.is_success());
+
+        let fee = U256::from(outcome.gas_used) * gas_price;
+        assert_eq!(changes.delta(CALLER, Asset::Eth), -I256::from_raw(value + fee));
+        assert_eq!(changes.delta(TOKEN, Asset::Eth), I256::from_raw(value));
+        assert_eq!(changes.delta(CALLER, Asset::Erc20(TOKEN)), I256::try_from(-100).unwrap());
+        assert_eq!(changes.delta(RECEIVER, Asset::Erc20(TOKEN)), I256::try_from(100).unwrap());
+        assert_eq!(changes.delta(RECEIVER, Asset::Eth), I256::ZERO);
+
+        // the zero address miner receives no priority fee
+        assert_eq!(changes.changes().len(), 4);
+        assert_eq!(changes.tokens(), vec![TOKEN]);
+    }
+}
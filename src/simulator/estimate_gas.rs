@@ -0,0 +1,185 @@
+use alloy::primitives::{ Bytes, U256 };
+use revm::interpreter::gas::validate_initial_tx_gas;
+use revm::primitives::{ HaltReason, TxEnv };
+use revm::Database;
+
+use super::{ SimOutcome, SimStatus, Simulator };
+
+
+/// The reason a gas limit could not be estimated
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EstimateGasError {
+    /// The transaction reverts even with the maximum gas limit
+    #[error("execution reverted: {reason}")]
+    Reverted { reason: String, output: Bytes },
+
+    /// The transaction halts for a reason other than running out of gas, eg. an invalid opcode
+    #[error("execution halted: {0:?}")]
+    Halted(HaltReason),
+
+    /// The transaction runs out of gas even with the maximum gas limit
+    #[error("gas required exceeds allowance ({0})")]
+    GasRequiredExceedsAllowance(u64),
+}
+
+impl Simulator {
+    /// Finds the lowest gas limit the transaction succeeds with, without committing any state changes
+    ///
+    /// Binary searches between the intrinsic gas and the lowest of `tx.gas_limit`, the gas limit
+    /// of the block header (also in [crate::EvmMode::Relaxed]) and the gas the caller can pay for.
+    /// Each step executes the transaction, so the gas withheld from calls by the 63/64 rule
+    /// and the gas needed before refunds are accounted for
+    ///
+    /// Transactions that fail regardless of gas return an [EstimateGasError]
+    pub fn estimate_gas(&mut self, tx: TxEnv) -> Result<u64, anyhow::Error> {
+        // the EVM block gas limit is unbounded in relaxed mode
+        let mut hi = tx.gas_limit.min(self.block_gas_limit);
+
+        // the caller must be able to pay for the gas limit
+        if tx.gas_price > U256::ZERO {
+            let balance = self.evm.db_mut().basic(tx.caller)?.map(|i| i.balance).unwrap_or_default();
+            let allowance = balance.saturating_sub(tx.value) / tx.gas_price;
+            hi = hi.min(allowance.saturating_to::<u64>());
+        }
+
+        let intrinsic_gas = validate_initial_tx_gas(
+            self.evm.spec_id(),
+            &tx.data,
+            tx.transact_to.is_create(),
+            &tx.access_list,
+            tx.authorization_list.as_ref().map(|l| l.len() as u64).unwrap_or_default()
+        );
+        if hi < intrinsic_gas {
+            return Err(EstimateGasError::GasRequiredExceedsAllowance(hi).into());
+        }
+
+        // a transaction that fails with all the gas available fails with any gas limit
+        let outcome = self.call_with_gas(&tx, hi)?;
+        match outcome.status {
+            SimStatus::Success => {}
+            SimStatus::Revert(reason) => {
+                return Err(EstimateGasError::Reverted { reason, output: outcome.output }.into());
+            }
+            SimStatus::Halt(HaltReason::OutOfGas(_)) => {
+                return Err(EstimateGasError::GasRequiredExceedsAllowance(hi).into());
+            }
+            SimStatus::Halt(reason) => {
+                return Err(EstimateGasError::Halted(reason).into());
+            }
+        }
+
+        // `gas_used` is after the refund, the transaction can't succeed with less
+        let mut lo = outcome.gas_used.max(intrinsic_gas) - 1;
+
+        // Most transactions succeed with the gas spent before the refund plus what the
+        // 63/64 rule withholds from calls, try it first to skip most of the search
+        let optimistic = ((outcome.gas_used + outcome.gas_refunded) * 64) / 63;
+        if optimistic > lo && optimistic < hi {
+            if self.call_with_gas(&tx, optimistic)?.is_success() {
+                hi = optimistic;
+            } else {
+                lo = optimistic;
+            }
+        }
+
+        while lo + 1 < hi {
+            let mid = lo + (hi - lo) / 2;
+            // reverts at a lower limit are out of gas errors of inner calls
+            if self.call_with_gas(&tx, mid)?.is_success() {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+
+        Ok(hi)
+    }
+
+    fn call_with_gas(&mut self, tx: &TxEnv, gas_limit: u64) -> Result<SimOutcome, anyhow::Error> {
+        let mut tx = tx.clone();
+        tx.gas_limit = gas_limit;
+        self.call_env(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvmMode;
+    use alloy::primitives::{ address, bytes, hex, Address };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode, TransactTo };
+
+    const CALLER: Address = address!("1111111111111111111111111111111111111111");
+    const OUTER: Address = address!("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    const BURNER: Address = address!("bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb");
+    const REVERTER: Address = address!("cccccccccccccccccccccccccccccccccccccccc");
+
+    fn offline_simulator(caller_balance: U256) -> Simulator {
+        // CALL(gas, BURNER, 0, 0, 0, 0, 0) and revert if it failed
+        let mut outer = hex!("6000600060006000600073").to_vec();
+        outer.extend_from_slice(BURNER.as_slice());
+        outer.extend_from_slice(&hex!("5af115602657005b60006000fd"));
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CALLER, AccountInfo { balance: caller_balance, ..Default::default() });
+        db.insert_account_info(OUTER, AccountInfo {
+            code: Some(Bytecode::new_raw(outer.into())),
+            ..Default::default()
+        });
+        // SSTORE(0, 1) SSTORE(1, 1)
+        db.insert_account_info(BURNER, AccountInfo {
+            code: Some(Bytecode::new_raw(bytes!("6001600055600160015500"))),
+            ..Default::default()
+        });
+        // REVERT(0, 0)
+        db.insert_account_info(REVERTER, AccountInfo {
+            code: Some(Bytecode::new_raw(bytes!("60006000fd"))),
+            ..Default::default()
+        });
+        Simulator::offline(db, EvmMode::Relaxed)
+    }
+
+    fn tx(to: Address, gas_price: u64) -> TxEnv {
+        TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(to),
+            gas_limit: 1_000_000,
+            gas_price: U256::from(gas_price),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn inner_call_needs_the_withheld_gas() {
+        let mut simulator = offline_simulator(U256::ZERO);
+        let gas_used = simulator.call_env(tx(OUTER, 0)).unwrap().gas_used;
+
+        let estimate = simulator.estimate_gas(tx(OUTER, 0)).unwrap();
+        assert!(estimate > gas_used);
+        assert!(simulator.call_with_gas(&tx(OUTER, 0), estimate).unwrap().is_success());
+        assert!(!simulator.call_with_gas(&tx(OUTER, 0), estimate - 1).unwrap().is_success());
+    }
+
+    #[test]
+    fn always_reverts() {
+        let mut simulator = offline_simulator(U256::ZERO);
+        let err = simulator.estimate_gas(tx(REVERTER, 0)).unwrap_err();
+
+        let err = err.downcast::<EstimateGasError>().unwrap();
+        assert!(matches!(err, EstimateGasError::Reverted { .. }));
+    }
+
+    #[test]
+    fn gas_required_exceeds_allowance() {
+        // enough for the intrinsic gas but not for the two stores
+        let mut simulator = offline_simulator(U256::from(30_000));
+        let err = simulator.estimate_gas(tx(BURNER, 1)).unwrap_err().downcast::<EstimateGasError>().unwrap();
+        assert_eq!(err, EstimateGasError::GasRequiredExceedsAllowance(30_000));
+
+        // not even enough for the intrinsic gas
+        let mut simulator = offline_simulator(U256::from(20_000));
+        let err = simulator.estimate_gas(tx(BURNER, 1)).unwrap_err().downcast::<EstimateGasError>().unwrap();
+        assert_eq!(err, EstimateGasError::GasRequiredExceedsAllowance(20_000));
+    }
+}
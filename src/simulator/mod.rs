@@ -0,0 +1,343 @@
+pub mod estimate_gas;
+pub mod access_list;
+pub mod trace;
+pub mod asset_changes;
+pub use estimate_gas::*;
+pub use access_list::*;
+pub use asset_changes::*;
+
+use alloy::primitives::{ Address, Bytes, Log, B256, U256 };
+use alloy::rpc::types::eth::Block;
+
+use revm::primitives::{ EVMError, EvmState, ExecutionResult, HaltReason, Output, TransactTo, TxEnv };
+use revm::{ inspector_handle_register, Database, DatabaseCommit, Evm, Inspector };
+
+use crate::forked_db::{ fork_db::ForkDB, database_error::DatabaseError };
+use crate::validation::TxRejection;
+use crate::deploy::{ create2_address, encode_create2_deploy, CREATE2_DEPLOYER };
+use crate::utils::revert_msg;
+use crate::tx_env::decode_raw_tx;
+use crate::{ new_evm_with_mode, EvmMode };
+
+
+/// The status of a simulated transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimStatus {
+    Success,
+
+    /// Reverted by the `REVERT` opcode, holds the decoded revert message
+    Revert(String),
+
+    /// Halted by the EVM, all gas is spent
+    Halt(HaltReason),
+}
+
+/// The outcome of a simulated transaction
+#[derive(Debug, Clone)]
+pub struct SimOutcome {
+    pub status: SimStatus,
+
+    /// Return data of the call, the revert data or the runtime code of a deployed contract
+    pub output: Bytes,
+
+    pub gas_used: u64,
+
+    pub gas_refunded: u64,
+
+    pub logs: Vec<Log>,
+
+    /// Address of the deployed contract, only set by [Simulator::deploy] and [Simulator::deploy_create2]
+    pub created_address: Option<Address>,
+
+    /// Accounts touched by the transaction and their new state
+    pub state_changes: EvmState,
+}
+
+impl SimOutcome {
+    pub(crate) fn new(result: ExecutionResult, state_changes: EvmState) -> Self {
+        let (status, output, gas_used, gas_refunded, logs, created_address) = match result {
+            ExecutionResult::Success { gas_used, gas_refunded, logs, output, .. } => {
+                let (output, created_address) = match output {
+                    Output::Call(bytes) => (bytes, None),
+                    Output::Create(bytes, address) => (bytes, address),
+                };
+                (SimStatus::Success, output, gas_used, gas_refunded, logs, created_address)
+            }
+            ExecutionResult::Revert { gas_used, output } => {
+                (SimStatus::Revert(revert_msg(&output)), output, gas_used, 0, Vec::new(), None)
+            }
+            ExecutionResult::Halt { reason, gas_used } => {
+                (SimStatus::Halt(reason), Bytes::new(), gas_used, 0, Vec::new(), None)
+            }
+        };
+
+        Self {
+            status,
+            output,
+            gas_used,
+            gas_refunded,
+            logs,
+            created_address,
+            state_changes,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == SimStatus::Success
+    }
+}
+
+/// High level wrapper around [Evm] and [ForkDB]
+///
+/// Takes care of filling the transaction environment and sets the caller nonce from the fork state
+/// so the same caller can send many transactions in a row
+pub struct Simulator {
+    pub evm: Evm<'static, (), ForkDB>,
+
+    /// Gas limit of the block header, also kept in [EvmMode::Relaxed] where the EVM doesn't check it
+    pub block_gas_limit: u64,
+
+    /// Base fee of the block header, [EvmMode::Relaxed] executes with a base fee of 0
+    pub basefee: U256,
+}
+
+impl Simulator {
+    /// Creates a new [Simulator] with the [EvmMode::Relaxed] checks
+    pub fn new(fork_db: ForkDB, block: Block) -> Self {
+        Self::with_mode(fork_db, block, EvmMode::Relaxed)
+    }
+
+    pub fn with_mode(fork_db: ForkDB, block: Block, mode: EvmMode) -> Self {
+        Self {
+            block_gas_limit: block.header.gas_limit as u64,
+            basefee: U256::from(block.header.base_fee_per_gas.unwrap_or_default()),
+            evm: new_evm_with_mode(fork_db, block, mode),
+        }
+    }
+
+    /// Returns the current nonce of an account
+    pub fn nonce(&mut self, address: Address) -> Result<u64, anyhow::Error> {
+        let info = self.evm.db_mut().basic(address)?;
+        Ok(info.map(|i| i.nonce).unwrap_or_default())
+    }
+
+    /// Executes a call without committing the state changes
+    pub fn call(
+        &mut self,
+        caller: Address,
+        to: Address,
+        data: Bytes,
+        value: U256
+    ) -> Result<SimOutcome, anyhow::Error> {
+        self.fill_tx(caller, TransactTo::Call(to), data, value)?;
+        self.execute(false)
+    }
+
+    /// Executes a transaction and commits the state changes
+    pub fn transact(
+        &mut self,
+        caller: Address,
+        to: Address,
+        data: Bytes,
+        value: U256
+    ) -> Result<SimOutcome, anyhow::Error> {
+        self.fill_tx(caller, TransactTo::Call(to), data, value)?;
+        self.execute(true)
+    }
+
+    /// Deploys a contract from its creation bytecode and commits the state changes
+    ///
+    /// The address of the new contract is in [SimOutcome::created_address]
+    pub fn deploy(
+        &mut self,
+        caller: Address,
+        code: Bytes,
+        value: U256
+    ) -> Result<SimOutcome, anyhow::Error> {
+        self.fill_tx(caller, TransactTo::Create, code, value)?;
+        self.execute(true)
+    }
+
+    /// Deploys a contract with `CREATE2` through the [CREATE2_DEPLOYER] proxy and commits the state changes
+    ///
+    /// Use [crate::deploy::ContractArtifact::creation_code] to get the `init_code` with constructor arguments
+    pub fn deploy_create2(
+        &mut self,
+        caller: Address,
+        salt: B256,
+        init_code: Bytes,
+        value: U256
+    ) -> Result<SimOutcome, anyhow::Error> {
+        let data = encode_create2_deploy(salt, &init_code);
+        let mut outcome = self.transact(caller, CREATE2_DEPLOYER, data, value)?;
+
+        if outcome.is_success() {
+            outcome.created_address = Some(create2_address(CREATE2_DEPLOYER, salt, &init_code));
+        }
+        Ok(outcome)
+    }
+
+    /// Executes a fully specified transaction without committing the state changes
+    pub fn call_env(&mut self, tx: TxEnv) -> Result<SimOutcome, anyhow::Error> {
+        *self.evm.tx_mut() = tx;
+        self.execute(false)
+    }
+
+    /// Executes a fully specified transaction and commits the state changes
+    pub fn transact_env(&mut self, tx: TxEnv) -> Result<SimOutcome, anyhow::Error> {
+        *self.evm.tx_mut() = tx;
+        self.execute(true)
+    }
+
+    /// Executes a transaction with an inspector attached, without committing the state changes
+    ///
+    /// Returns the inspector so the recorded data can be read
+    pub fn inspect<I>(&mut self, tx: TxEnv, inspector: I) -> Result<(SimOutcome, I), anyhow::Error>
+        where I: for<'a> Inspector<&'a mut ForkDB>
+    {
+        *self.evm.tx_mut() = tx;
+        let env = self.evm.context.evm.env.clone();
+        let spec_id = self.evm.spec_id();
+
+        let mut evm = Evm::builder()
+            .with_db(self.evm.db_mut())
+            .with_external_context(inspector)
+            .with_env(env)
+            .with_spec_id(spec_id)
+            .append_handler_register(inspector_handle_register)
+            .build();
+        let res = evm.transact();
+        let inspector = evm.into_context().external;
+
+        let res = res.map_err(|e| self.to_error(e))?;
+        Ok((SimOutcome::new(res.result, res.state), inspector))
+    }
+
+    /// Executes a signed raw transaction without committing the state changes
+    ///
+    /// The sender is recovered from the signature and every field of the transaction is kept,
+    /// so the nonce, fees and chain id are checked like the node would
+    pub fn call_raw(&mut self, raw: &[u8]) -> Result<SimOutcome, anyhow::Error> {
+        let tx = decode_raw_tx(raw)?;
+        self.call_env(tx.tx_env)
+    }
+
+    /// Executes a signed raw transaction and commits the state changes
+    pub fn transact_raw(&mut self, raw: &[u8]) -> Result<SimOutcome, anyhow::Error> {
+        let tx = decode_raw_tx(raw)?;
+        self.transact_env(tx.tx_env)
+    }
+
+    fn fill_tx(
+        &mut self,
+        caller: Address,
+        transact_to: TransactTo,
+        data: Bytes,
+        value: U256
+    ) -> Result<(), anyhow::Error> {
+        let nonce = self.nonce(caller)?;
+
+        // a fresh environment, nothing is left over from a previous `call_env` or `inspect`
+        *self.evm.tx_mut() = TxEnv {
+            caller,
+            transact_to,
+            data,
+            value,
+            nonce: Some(nonce),
+            gas_limit: self.block_gas_limit,
+            gas_price: self.evm.block().basefee,
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    // Runs `f` with the base fee of the block header, so the gas fees are the ones paid on chain
+    pub(crate) fn with_header_basefee<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let basefee = std::mem::replace(&mut self.evm.block_mut().basefee, self.basefee);
+        let res = f(self);
+        self.evm.block_mut().basefee = basefee;
+        res
+    }
+
+    fn execute(&mut self, commit: bool) -> Result<SimOutcome, anyhow::Error> {
+        let res = self.evm.transact().map_err(|e| self.to_error(e))?;
+
+        if commit {
+            self.evm.db_mut().commit(res.state.clone());
+        }
+
+        Ok(SimOutcome::new(res.result, res.state))
+    }
+
+    // Invalid transactions are returned as a [TxRejection] so they can be downcasted
+    fn to_error(&self, err: EVMError<DatabaseError>) -> anyhow::Error {
+        match TxRejection::from_evm_error(&err, &self.evm.context.evm.env) {
+            Some(rejection) => rejection.into(),
+            None => anyhow::anyhow!("Failed to execute transaction: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Simulator {
+    // A simulator on an offline fork of block 1, with a 30M gas limit and a 1 gwei base fee
+    pub(crate) fn offline(db: revm::db::CacheDB<revm::db::EmptyDB>, mode: EvmMode) -> Self {
+        let mut block = Block::default();
+        block.header.number = Some(1);
+        block.header.gas_limit = 30_000_000;
+        block.header.base_fee_per_gas = Some(1_000_000_000);
+        Simulator::with_mode(ForkDB::offline(db), block, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::AccountInfo;
+
+    const CALLER: Address = address!("1111111111111111111111111111111111111111");
+
+    fn strict_simulator() -> Simulator {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CALLER, AccountInfo {
+            balance: U256::from(10).pow(U256::from(18)),
+            ..Default::default()
+        });
+        Simulator::offline(db, EvmMode::Strict)
+    }
+
+    #[test]
+    fn strict_call() {
+        let mut simulator = strict_simulator();
+        let to = address!("2222222222222222222222222222222222222222");
+
+        let outcome = simulator.call(CALLER, to, Bytes::new(), U256::from(1)).unwrap();
+        assert!(outcome.is_success());
+
+        let outcome = simulator.transact(CALLER, to, Bytes::new(), U256::from(1)).unwrap();
+        assert!(outcome.is_success());
+        assert_eq!(simulator.nonce(CALLER).unwrap(), 1);
+    }
+
+    #[test]
+    fn strict_call_after_call_env() {
+        let mut simulator = strict_simulator();
+        let to = address!("2222222222222222222222222222222222222222");
+
+        // rejected for its chain id, which must not leak into the next call
+        let tx = TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(to),
+            gas_limit: 21_000,
+            gas_price: U256::from(1_000_000_000),
+            chain_id: Some(5),
+            ..Default::default()
+        };
+        assert!(simulator.call_env(tx).is_err());
+
+        let outcome = simulator.call(CALLER, to, Bytes::new(), U256::ZERO).unwrap();
+        assert!(outcome.is_success());
+    }
+}
@@ -0,0 +1,65 @@
+use revm::primitives::TxEnv;
+
+use super::{ SimOutcome, Simulator };
+use crate::inspectors::{
+    CallFrame,
+    CallTracer,
+    CallTracerConfig,
+    GasProfile,
+    GasProfiler,
+    ParityTracer,
+    StateDiff,
+    StructLogResult,
+    StructLogger,
+    StructLoggerConfig,
+    TraceResults,
+    TraceType,
+};
+
+
+impl Simulator {
+    /// Executes a transaction without committing the state changes and returns its call tree,
+    /// like `debug_traceCall` with geth's `callTracer`
+    pub fn call_trace(
+        &mut self,
+        tx: TxEnv,
+        config: CallTracerConfig
+    ) -> Result<(SimOutcome, CallFrame), anyhow::Error> {
+        let gas_limit = tx.gas_limit;
+        let (outcome, tracer) = self.inspect(tx, CallTracer::new(config))?;
+
+        let frame = tracer
+            .into_frame(gas_limit, outcome.gas_used)
+            .ok_or_else(|| anyhow::anyhow!("Transaction made no calls"))?;
+        Ok((outcome, frame))
+    }
+
+    /// Executes a transaction without committing the state changes and returns the requested
+    /// Parity style traces, like `trace_call`
+    pub fn trace_call(&mut self, tx: TxEnv, trace_types: &[TraceType]) -> Result<TraceResults, anyhow::Error> {
+        let (outcome, tracer) = self.inspect(tx, ParityTracer::new(trace_types))?;
+
+        // nothing is committed, the database still has the state before the transaction
+        let state_diff = match trace_types.contains(&TraceType::StateDiff) {
+            true => Some(StateDiff::new(&outcome.state_changes, self.evm.db_mut())?),
+            false => None,
+        };
+        Ok(tracer.into_results(outcome.output, state_diff))
+    }
+
+    /// Executes a transaction without committing the state changes and returns every executed opcode,
+    /// like `debug_traceCall` with geth's default tracer
+    pub fn struct_logs(&mut self, tx: TxEnv, config: StructLoggerConfig) -> Result<StructLogResult, anyhow::Error> {
+        let (outcome, logger) = self.inspect(tx, StructLogger::new(config))?;
+        Ok(logger.into_result(outcome.gas_used, !outcome.is_success(), &outcome.output))
+    }
+
+    /// Executes a transaction without committing the state changes and returns the gas spent
+    /// by each call frame, contract and function
+    pub fn profile_gas(&mut self, tx: TxEnv) -> Result<(SimOutcome, GasProfile), anyhow::Error> {
+        let (outcome, profiler) = self.inspect(tx, GasProfiler::new())?;
+
+        let profile = profiler.into_profile().ok_or_else(|| anyhow::anyhow!("Transaction made no calls"))?;
+        Ok((outcome, profile))
+    }
+}
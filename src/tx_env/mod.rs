@@ -0,0 +1,415 @@
+use alloy::consensus::{ Signed, Transaction, TxEnvelope };
+use alloy::eips::eip2718::Decodable2718;
+use alloy::primitives::{ Address, B256, U256 };
+use alloy::rpc::types::eth::{ Transaction as RpcTransaction, TransactionRequest };
+use revm::primitives::{ TransactTo, TxEnv };
+
+
+/// A signed transaction decoded from its raw bytes
+#[derive(Debug, Clone)]
+pub struct DecodedTx {
+    pub hash: B256,
+
+    /// Sender recovered from the signature
+    pub from: Address,
+
+    pub envelope: TxEnvelope,
+
+    /// The transaction mapped to a [TxEnv], ready to be simulated
+    pub tx_env: TxEnv,
+}
+
+/// Decodes an RLP encoded signed transaction, as sent with `eth_sendRawTransaction`
+///
+/// Supports legacy, EIP-2930, EIP-1559 and EIP-4844 (with or without the blob sidecar) transactions
+pub fn decode_raw_tx(mut raw: &[u8]) -> Result<DecodedTx, anyhow::Error> {
+    let envelope = TxEnvelope::decode_2718(&mut raw)?;
+    let from = envelope.recover_signer()?;
+    let tx_env = tx_env_from_envelope(&envelope, from)?;
+
+    Ok(DecodedTx {
+        hash: *envelope.tx_hash(),
+        from,
+        envelope,
+        tx_env,
+    })
+}
+
+/// Maps every field of a signed transaction into a [TxEnv]
+pub fn tx_env_from_envelope(envelope: &TxEnvelope, from: Address) -> Result<TxEnv, anyhow::Error> {
+    let mut tx_env = TxEnv {
+        caller: from,
+        ..Default::default()
+    };
+
+    match envelope {
+        TxEnvelope::Legacy(signed) => {
+            let tx = signed.tx();
+            fill_common(&mut tx_env, signed);
+            tx_env.transact_to = tx.to;
+            tx_env.gas_price = U256::from(tx.gas_price);
+            tx_env.chain_id = tx.chain_id;
+        }
+        TxEnvelope::Eip2930(signed) => {
+            let tx = signed.tx();
+            fill_common(&mut tx_env, signed);
+            tx_env.transact_to = tx.to;
+            tx_env.gas_price = U256::from(tx.gas_price);
+            tx_env.chain_id = Some(tx.chain_id);
+            tx_env.access_list = tx.access_list.0.clone();
+        }
+        TxEnvelope::Eip1559(signed) => {
+            let tx = signed.tx();
+            fill_common(&mut tx_env, signed);
+            tx_env.transact_to = tx.to;
+            tx_env.gas_price = U256::from(tx.max_fee_per_gas);
+            tx_env.gas_priority_fee = Some(U256::from(tx.max_priority_fee_per_gas));
+            tx_env.chain_id = Some(tx.chain_id);
+            tx_env.access_list = tx.access_list.0.clone();
+        }
+        TxEnvelope::Eip4844(signed) => {
+            let tx = signed.tx().tx();
+            fill_common(&mut tx_env, signed);
+            // blob transactions can not create contracts
+            tx_env.transact_to = TransactTo::Call(tx.to);
+            tx_env.gas_price = U256::from(tx.max_fee_per_gas);
+            tx_env.gas_priority_fee = Some(U256::from(tx.max_priority_fee_per_gas));
+            tx_env.chain_id = Some(tx.chain_id);
+            tx_env.access_list = tx.access_list.0.clone();
+            tx_env.blob_hashes = tx.blob_versioned_hashes.clone();
+            tx_env.max_fee_per_blob_gas = Some(U256::from(tx.max_fee_per_blob_gas));
+        }
+        _ => {
+            return Err(anyhow::anyhow!("Unsupported transaction type {:?}", envelope.tx_type()));
+        }
+    }
+
+    Ok(tx_env)
+}
+
+/// Maps every field of an RPC transaction into a [TxEnv], eg. a pending transaction from the mempool
+///
+/// Transactions without a `to` address are contract creations.
+/// For EIP-1559 and EIP-4844 transactions the max fee is used as the gas price,
+/// the `gasPrice` field of mined transactions is the effective price and is ignored
+pub fn tx_env_from_rpc(tx: &RpcTransaction) -> TxEnv {
+    let transact_to = match tx.to {
+        Some(to) => TransactTo::Call(to),
+        None => TransactTo::Create,
+    };
+
+    let (gas_price, gas_priority_fee) = match tx.max_fee_per_gas {
+        Some(max_fee) => {
+            (U256::from(max_fee), Some(U256::from(tx.max_priority_fee_per_gas.unwrap_or_default())))
+        }
+        None => (U256::from(tx.gas_price.unwrap_or_default()), None),
+    };
+
+    TxEnv {
+        caller: tx.from,
+        gas_limit: tx.gas as u64,
+        gas_price,
+        transact_to,
+        value: tx.value,
+        data: tx.input.clone(),
+        nonce: Some(tx.nonce),
+        chain_id: tx.chain_id,
+        access_list: tx.access_list.clone().map(|list| list.0).unwrap_or_default(),
+        gas_priority_fee,
+        blob_hashes: tx.blob_versioned_hashes.clone().unwrap_or_default(),
+        max_fee_per_blob_gas: tx.max_fee_per_blob_gas.map(U256::from),
+        ..Default::default()
+    }
+}
+
+/// Maps a call request, as sent with `eth_call` or `eth_estimateGas`, into a [TxEnv]
+///
+/// Missing fields use the defaults of a node: zero gas price and value, `gas_limit` as gas
+/// and no nonce check unless the nonce is set
+pub fn tx_env_from_request(request: &TransactionRequest, gas_limit: u64) -> TxEnv {
+    let gas_price = request.max_fee_per_gas.or(request.gas_price).unwrap_or_default();
+
+    TxEnv {
+        caller: request.from.unwrap_or_default(),
+        gas_limit: request.gas.map(|gas| gas as u64).unwrap_or(gas_limit),
+        gas_price: U256::from(gas_price),
+        transact_to: request.to.unwrap_or(TransactTo::Create),
+        value: request.value.unwrap_or_default(),
+        data: request.input.input().cloned().unwrap_or_default(),
+        nonce: request.nonce,
+        chain_id: request.chain_id,
+        access_list: request.access_list.clone().map(|list| list.0).unwrap_or_default(),
+        gas_priority_fee: request.max_priority_fee_per_gas.map(U256::from),
+        blob_hashes: request.blob_versioned_hashes.clone().unwrap_or_default(),
+        max_fee_per_blob_gas: request.max_fee_per_blob_gas.map(U256::from),
+        ..Default::default()
+    }
+}
+
+// Fields shared by every transaction type
+fn fill_common<T: Transaction>(tx_env: &mut TxEnv, signed: &Signed<T>) {
+    let tx = signed.tx();
+    tx_env.nonce = Some(tx.nonce());
+    tx_env.gas_limit = tx.gas_limit() as u64;
+    tx_env.value = tx.value();
+    tx_env.data = tx.input().to_vec().into();
+}
+
+// Signs a transaction and returns its raw bytes, as sent with `eth_sendRawTransaction`
+#[cfg(test)]
+pub(crate) fn sign_raw_tx<T>(
+    signer: &alloy::signers::local::PrivateKeySigner,
+    mut tx: T
+) -> alloy::primitives::Bytes
+    where T: alloy::consensus::SignableTransaction<alloy::primitives::Signature>, Signed<T>: Into<TxEnvelope>
+{
+    use alloy::eips::eip2718::Encodable2718;
+    use alloy::network::TxSignerSync;
+
+    let signature = signer.sign_transaction_sync(&mut tx).expect("the transaction can be signed");
+    let envelope: TxEnvelope = tx.into_signed(signature).into();
+    envelope.encoded_2718().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::consensus::{ TxEip1559, TxEip2930, TxEip4844, TxLegacy };
+    use alloy::eips::eip2930::{ AccessList, AccessListItem };
+    use alloy::primitives::{ address, Bytes, TxKind };
+    use alloy::signers::local::PrivateKeySigner;
+
+    fn base_tx(transaction_type: u8) -> RpcTransaction {
+        RpcTransaction {
+            from: address!("1111111111111111111111111111111111111111"),
+            to: Some(address!("2222222222222222222222222222222222222222")),
+            value: U256::from(100),
+            gas: 60_000,
+            nonce: 7,
+            input: Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]),
+            chain_id: Some(1),
+            transaction_type: Some(transaction_type),
+            ..Default::default()
+        }
+    }
+
+    fn access_list() -> AccessList {
+        AccessList(
+            vec![AccessListItem {
+                address: address!("3333333333333333333333333333333333333333"),
+                storage_keys: vec![B256::with_last_byte(1)],
+            }]
+        )
+    }
+
+    #[test]
+    fn legacy() {
+        let tx = RpcTransaction {
+            gas_price: Some(20),
+            ..base_tx(0)
+        };
+        let env = tx_env_from_rpc(&tx);
+
+        assert_eq!(env.caller, tx.from);
+        assert_eq!(env.transact_to, TransactTo::Call(tx.to.unwrap()));
+        assert_eq!(env.value, U256::from(100));
+        assert_eq!(env.data, tx.input);
+        assert_eq!(env.gas_limit, 60_000);
+        assert_eq!(env.gas_price, U256::from(20));
+        assert_eq!(env.gas_priority_fee, None);
+        assert_eq!(env.nonce, Some(7));
+        assert_eq!(env.chain_id, Some(1));
+        assert!(env.access_list.is_empty());
+    }
+
+    #[test]
+    fn legacy_contract_creation() {
+        let tx = RpcTransaction {
+            to: None,
+            gas_price: Some(20),
+            chain_id: None,
+            ..base_tx(0)
+        };
+        let env = tx_env_from_rpc(&tx);
+
+        assert_eq!(env.transact_to, TransactTo::Create);
+        assert_eq!(env.chain_id, None);
+    }
+
+    #[test]
+    fn eip2930() {
+        let tx = RpcTransaction {
+            gas_price: Some(20),
+            access_list: Some(access_list()),
+            ..base_tx(1)
+        };
+        let env = tx_env_from_rpc(&tx);
+
+        assert_eq!(env.gas_price, U256::from(20));
+        assert_eq!(env.gas_priority_fee, None);
+        assert_eq!(env.access_list, access_list().0);
+    }
+
+    #[test]
+    fn eip1559() {
+        let tx = RpcTransaction {
+            // effective gas price of a mined transaction
+            gas_price: Some(15),
+            max_fee_per_gas: Some(30),
+            max_priority_fee_per_gas: Some(2),
+            access_list: Some(access_list()),
+            ..base_tx(2)
+        };
+        let env = tx_env_from_rpc(&tx);
+
+        assert_eq!(env.gas_price, U256::from(30));
+        assert_eq!(env.gas_priority_fee, Some(U256::from(2)));
+        assert_eq!(env.access_list, access_list().0);
+        assert_eq!(env.max_fee_per_blob_gas, None);
+        assert!(env.blob_hashes.is_empty());
+    }
+
+    #[test]
+    fn eip1559_contract_creation() {
+        let tx = RpcTransaction {
+            to: None,
+            max_fee_per_gas: Some(30),
+            max_priority_fee_per_gas: Some(2),
+            ..base_tx(2)
+        };
+        let env = tx_env_from_rpc(&tx);
+
+        assert_eq!(env.transact_to, TransactTo::Create);
+        assert_eq!(env.data, tx.input);
+    }
+
+    #[test]
+    fn eip4844() {
+        let blob_hash = B256::repeat_byte(1);
+        let tx = RpcTransaction {
+            max_fee_per_gas: Some(30),
+            max_priority_fee_per_gas: Some(2),
+            max_fee_per_blob_gas: Some(5),
+            blob_versioned_hashes: Some(vec![blob_hash]),
+            access_list: Some(access_list()),
+            ..base_tx(3)
+        };
+        let env = tx_env_from_rpc(&tx);
+
+        assert_eq!(env.gas_price, U256::from(30));
+        assert_eq!(env.gas_priority_fee, Some(U256::from(2)));
+        assert_eq!(env.blob_hashes, vec![blob_hash]);
+        assert_eq!(env.max_fee_per_blob_gas, Some(U256::from(5)));
+        assert_eq!(env.access_list, access_list().0);
+    }
+
+    fn signer() -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&B256::repeat_byte(0x42)).unwrap()
+    }
+
+    const TO: Address = address!("2222222222222222222222222222222222222222");
+
+    #[test]
+    fn decode_legacy() {
+        let tx = TxLegacy {
+            chain_id: Some(1),
+            nonce: 7,
+            gas_price: 20,
+            gas_limit: 60_000,
+            to: TxKind::Call(TO),
+            value: U256::from(100),
+            input: Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]),
+        };
+        let decoded = decode_raw_tx(&sign_raw_tx(&signer(), tx)).unwrap();
+        let env = decoded.tx_env;
+
+        assert_eq!(decoded.from, signer().address());
+        assert_eq!(env.caller, signer().address());
+        assert_eq!(env.transact_to, TransactTo::Call(TO));
+        assert_eq!(env.value, U256::from(100));
+        assert_eq!(env.data, Bytes::from(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(env.gas_limit, 60_000);
+        assert_eq!(env.gas_price, U256::from(20));
+        assert_eq!(env.gas_priority_fee, None);
+        assert_eq!(env.nonce, Some(7));
+        assert_eq!(env.chain_id, Some(1));
+    }
+
+    #[test]
+    fn decode_eip2930() {
+        let tx = TxEip2930 {
+            chain_id: 1,
+            nonce: 1,
+            gas_price: 20,
+            gas_limit: 60_000,
+            to: TxKind::Create,
+            access_list: access_list(),
+            ..Default::default()
+        };
+        let decoded = decode_raw_tx(&sign_raw_tx(&signer(), tx)).unwrap();
+        let env = decoded.tx_env;
+
+        assert_eq!(env.caller, signer().address());
+        assert_eq!(env.transact_to, TransactTo::Create);
+        assert_eq!(env.gas_price, U256::from(20));
+        assert_eq!(env.gas_priority_fee, None);
+        assert_eq!(env.access_list, access_list().0);
+    }
+
+    #[test]
+    fn decode_eip1559() {
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce: 1,
+            gas_limit: 60_000,
+            max_fee_per_gas: 30,
+            max_priority_fee_per_gas: 2,
+            to: TxKind::Call(TO),
+            access_list: access_list(),
+            ..Default::default()
+        };
+        let raw = sign_raw_tx(&signer(), tx);
+        let decoded = decode_raw_tx(&raw).unwrap();
+        let env = decoded.tx_env;
+
+        assert_eq!(decoded.hash, alloy::primitives::keccak256(&raw));
+        assert_eq!(env.caller, signer().address());
+        assert_eq!(env.gas_price, U256::from(30));
+        assert_eq!(env.gas_priority_fee, Some(U256::from(2)));
+        assert_eq!(env.access_list, access_list().0);
+        assert!(env.blob_hashes.is_empty());
+    }
+
+    #[test]
+    fn decode_eip4844() {
+        let blob_hash = B256::repeat_byte(1);
+        let tx = TxEip4844 {
+            chain_id: 1,
+            nonce: 1,
+            gas_limit: 60_000,
+            max_fee_per_gas: 30,
+            max_priority_fee_per_gas: 2,
+            to: TO,
+            access_list: access_list(),
+            blob_versioned_hashes: vec![blob_hash],
+            max_fee_per_blob_gas: 5,
+            ..Default::default()
+        };
+        let decoded = decode_raw_tx(&sign_raw_tx(&signer(), tx)).unwrap();
+        let env = decoded.tx_env;
+
+        assert_eq!(env.caller, signer().address());
+        assert_eq!(env.transact_to, TransactTo::Call(TO));
+        assert_eq!(env.gas_price, U256::from(30));
+        assert_eq!(env.gas_priority_fee, Some(U256::from(2)));
+        assert_eq!(env.access_list, access_list().0);
+        assert_eq!(env.blob_hashes, vec![blob_hash]);
+        assert_eq!(env.max_fee_per_blob_gas, Some(U256::from(5)));
+    }
+
+    #[test]
+    fn decode_garbage() {
+        assert!(decode_raw_tx(&[0xde, 0xad, 0xbe, 0xef]).is_err());
+        assert!(decode_raw_tx(&[]).is_err());
+    }
+}
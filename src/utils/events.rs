@@ -0,0 +1,243 @@
+use alloy::primitives::{ Address, Log };
+use alloy::sol;
+use alloy::sol_types::SolEvent;
+
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    event Transfer(address indexed from, address indexed to, uint256 value);
+
+    #[derive(Debug, PartialEq, Eq)]
+    event Approval(address indexed owner, address indexed spender, uint256 value);
+
+    #[derive(Debug, PartialEq, Eq)]
+    event Deposit(address indexed dst, uint256 wad);
+
+    #[derive(Debug, PartialEq, Eq)]
+    event Withdrawal(address indexed src, uint256 wad);
+}
+
+// The pools are in separate blocks, `sol!` renames events with the same name as overloads
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    interface IUniswapV2Pair {
+        event Swap(
+            address indexed sender,
+            uint256 amount0In,
+            uint256 amount1In,
+            uint256 amount0Out,
+            uint256 amount1Out,
+            address indexed to
+        );
+        event Sync(uint112 reserve0, uint112 reserve1);
+        event Mint(address indexed sender, uint256 amount0, uint256 amount1);
+        event Burn(address indexed sender, uint256 amount0, uint256 amount1, address indexed to);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq)]
+    interface IUniswapV3Pool {
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick
+        );
+        event Mint(
+            address sender,
+            address indexed owner,
+            int24 indexed tickLower,
+            int24 indexed tickUpper,
+            uint128 amount,
+            uint256 amount0,
+            uint256 amount1
+        );
+        event Burn(
+            address indexed owner,
+            int24 indexed tickLower,
+            int24 indexed tickUpper,
+            uint128 amount,
+            uint256 amount0,
+            uint256 amount1
+        );
+        event Collect(
+            address indexed owner,
+            address recipient,
+            int24 indexed tickLower,
+            int24 indexed tickUpper,
+            uint128 amount0,
+            uint128 amount1
+        );
+    }
+}
+
+/// The ERC20, WETH and Uniswap events that can be decoded from execution logs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodedEvent {
+    Transfer(Transfer),
+    Approval(Approval),
+
+    /// WETH
+    Deposit(Deposit),
+    Withdrawal(Withdrawal),
+
+    UniswapV2Swap(IUniswapV2Pair::Swap),
+    UniswapV2Sync(IUniswapV2Pair::Sync),
+    UniswapV2Mint(IUniswapV2Pair::Mint),
+    UniswapV2Burn(IUniswapV2Pair::Burn),
+
+    UniswapV3Swap(IUniswapV3Pool::Swap),
+    UniswapV3Mint(IUniswapV3Pool::Mint),
+    UniswapV3Burn(IUniswapV3Pool::Burn),
+    UniswapV3Collect(IUniswapV3Pool::Collect),
+}
+
+/// A decoded event with the address of the contract that emitted it, eg. the token or the pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedLog {
+    pub address: Address,
+    pub event: DecodedEvent,
+}
+
+/// Decodes a log into one of the known [DecodedEvent]s
+///
+/// Returns `None` for unknown events and for logs that only share the signature of a known event,
+/// eg. ERC721 `Transfer` has its `tokenId` indexed
+pub fn decode_log(log: &Log) -> Option<DecodedLog> {
+    let topic0 = *log.data.topics().first()?;
+
+    let event = match topic0 {
+        Transfer::SIGNATURE_HASH => DecodedEvent::Transfer(decode(log)?),
+        Approval::SIGNATURE_HASH => DecodedEvent::Approval(decode(log)?),
+        Deposit::SIGNATURE_HASH => DecodedEvent::Deposit(decode(log)?),
+        Withdrawal::SIGNATURE_HASH => DecodedEvent::Withdrawal(decode(log)?),
+        IUniswapV2Pair::Swap::SIGNATURE_HASH => DecodedEvent::UniswapV2Swap(decode(log)?),
+        IUniswapV2Pair::Sync::SIGNATURE_HASH => DecodedEvent::UniswapV2Sync(decode(log)?),
+        IUniswapV2Pair::Mint::SIGNATURE_HASH => DecodedEvent::UniswapV2Mint(decode(log)?),
+        IUniswapV2Pair::Burn::SIGNATURE_HASH => DecodedEvent::UniswapV2Burn(decode(log)?),
+        IUniswapV3Pool::Swap::SIGNATURE_HASH => DecodedEvent::UniswapV3Swap(decode(log)?),
+        IUniswapV3Pool::Mint::SIGNATURE_HASH => DecodedEvent::UniswapV3Mint(decode(log)?),
+        IUniswapV3Pool::Burn::SIGNATURE_HASH => DecodedEvent::UniswapV3Burn(decode(log)?),
+        IUniswapV3Pool::Collect::SIGNATURE_HASH => DecodedEvent::UniswapV3Collect(decode(log)?),
+        _ => {
+            return None;
+        }
+    };
+
+    Some(DecodedLog {
+        address: log.address,
+        event,
+    })
+}
+
+/// Decodes the known events of the logs, in the order they were emitted
+///
+/// Pass `ExecutionResult::logs()` or [crate::simulator::SimOutcome::logs]
+pub fn decode_logs(logs: &[Log]) -> Vec<DecodedLog> {
+    logs.iter().filter_map(decode_log).collect()
+}
+
+fn decode<E: SolEvent>(log: &Log) -> Option<E> {
+    E::decode_log_data(&log.data, true).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{ address, Bytes, B256, I256, U256 };
+    use alloy::sol_types::SolEvent;
+
+    const TOKEN: Address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+    const FROM: Address = address!("1111111111111111111111111111111111111111");
+    const TO: Address = address!("2222222222222222222222222222222222222222");
+
+    fn transfer_log() -> Log {
+        // as emitted on chain, the addresses are left padded topics and the value is the data
+        Log::new_unchecked(
+            TOKEN,
+            vec![Transfer::SIGNATURE_HASH, FROM.into_word(), TO.into_word()],
+            Bytes::from(U256::from(1000).to_be_bytes_vec())
+        )
+    }
+
+    fn emitted<E: SolEvent>(event: &E) -> Log {
+        let topics = event.encode_topics().into_iter().map(|topic| topic.0).collect();
+        Log::new_unchecked(TOKEN, topics, event.encode_data().into())
+    }
+
+    #[test]
+    fn transfer() {
+        let decoded = decode_log(&transfer_log()).unwrap();
+
+        assert_eq!(decoded.address, TOKEN);
+        assert_eq!(decoded.event, DecodedEvent::Transfer(Transfer { from: FROM, to: TO, value: U256::from(1000) }));
+    }
+
+    #[test]
+    fn erc721_transfer_is_not_decoded() {
+        // same signature, but the token id is indexed
+        let log = Log::new_unchecked(
+            TOKEN,
+            vec![Transfer::SIGNATURE_HASH, FROM.into_word(), TO.into_word(), B256::with_last_byte(1)],
+            Bytes::new()
+        );
+        assert_eq!(decode_log(&log), None);
+    }
+
+    #[test]
+    fn uniswap_v3_swap() {
+        let swap = IUniswapV3Pool::Swap {
+            sender: FROM,
+            recipient: TO,
+            amount0: I256::try_from(-1000).unwrap(),
+            amount1: I256::try_from(2000).unwrap(),
+            sqrtPriceX96: U256::from(1) << 96,
+            liquidity: 10_000,
+            tick: -200,
+        };
+        let log = emitted(&swap);
+
+        // only the sender and the recipient are topics
+        assert_eq!(log.data.topics(), &[IUniswapV3Pool::Swap::SIGNATURE_HASH, FROM.into_word(), TO.into_word()]);
+        assert_eq!(decode_log(&log).unwrap().event, DecodedEvent::UniswapV3Swap(swap));
+    }
+
+    #[test]
+    fn uniswap_v3_mint_with_indexed_ticks() {
+        let mint = IUniswapV3Pool::Mint {
+            sender: FROM,
+            owner: TO,
+            tickLower: -887_220,
+            tickUpper: 887_220,
+            amount: 1,
+            amount0: U256::from(2),
+            amount1: U256::from(3),
+        };
+        let log = emitted(&mint);
+
+        assert_eq!(log.data.topics().len(), 4);
+        assert_eq!(decode_log(&log).unwrap().event, DecodedEvent::UniswapV3Mint(mint));
+    }
+
+    #[test]
+    fn unknown_topic() {
+        let log = Log::new_unchecked(TOKEN, vec![B256::repeat_byte(0xab)], Bytes::new());
+        assert_eq!(decode_log(&log), None);
+
+        let anonymous = Log::new_unchecked(TOKEN, vec![], Bytes::new());
+        assert_eq!(decode_log(&anonymous), None);
+    }
+
+    #[test]
+    fn decode_logs_skips_unknown_events() {
+        let unknown = Log::new_unchecked(TOKEN, vec![B256::repeat_byte(0xab)], Bytes::new());
+        let decoded = decode_logs(&[unknown, transfer_log()]);
+
+        assert_eq!(decoded.len(), 1);
+        assert!(matches!(decoded[0].event, DecodedEvent::Transfer(_)));
+    }
+}
@@ -1,11 +1,23 @@
+pub mod events;
+pub mod revert;
+pub mod signatures;
+pub mod token_registry;
+
+pub use events::*;
+pub use revert::*;
+pub use signatures::*;
+pub use token_registry::*;
+
 use alloy::{
     primitives::{ Address, Bytes, U256 },
     providers::RootProvider,
     sol,
 };
 use alloy::pubsub::PubSubFrontend;
-use alloy::core::sol_types::SolCall;
-use revm::primitives::Bytecode;
+use alloy::core::sol_types::{ SolCall, SolValue };
+use revm::primitives::{ Bytecode, ExecutionResult, Output, TransactTo };
+use revm::Evm;
+use serde::{ Deserialize, Serialize };
 
 use std::sync::Arc;
 use std::str::FromStr;
@@ -13,6 +25,8 @@ use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
 
+use crate::forked_db::fork_db::ForkDB;
+
 
 sol! {
     #[sol(rpc)]
@@ -125,7 +139,8 @@ pub fn encode_recover_erc20(token: Address, amount: U256) -> Vec<u8> {
 }
 
 /// Struct that holds ERC20 token information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ERC20Token {
     pub address: Address,
     pub symbol: String,
@@ -153,6 +168,59 @@ impl ERC20Token {
         })
     }
 
+    /// Reads the metadata by executing calls on the fork instead of live RPC calls,
+    /// works offline once the state of the token is cached
+    ///
+    /// The state fetched by the calls is kept in `fork_db`, no state changes are committed
+    ///
+    /// Tolerates tokens that return `bytes32` for their name and symbol like MKR,
+    /// and tokens without `decimals` which then default to 0
+    pub fn from_fork(address: Address, fork_db: &mut ForkDB) -> Result<Self, anyhow::Error> {
+        let mut evm = Evm::builder()
+            .with_db(fork_db)
+            .modify_tx_env(|tx| {
+                tx.caller = Address::ZERO;
+                tx.transact_to = TransactTo::Call(address);
+                // a malicious token can't loop forever
+                tx.gas_limit = 1_000_000;
+            })
+            .build();
+
+        // the state changes are not committed, the calls can't affect each other
+        let mut call = |data: Vec<u8>| -> Result<Option<Bytes>, anyhow::Error> {
+            evm.tx_mut().data = data.into();
+            match evm.transact()?.result {
+                ExecutionResult::Success { output: Output::Call(bytes), .. } => Ok(Some(bytes)),
+                _ => Ok(None),
+            }
+        };
+
+        let symbol = call(ERC20::symbolCall {}.abi_encode())?;
+        let name = call(ERC20::nameCall {}.abi_encode())?;
+        let decimals = call(ERC20::decimalsCall {}.abi_encode())?;
+        let total_supply = call(ERC20::totalSupplyCall {}.abi_encode())?;
+
+        let symbol = symbol.and_then(|output| decode_string(&output));
+        let name = name.and_then(|output| decode_string(&output));
+        let decimals = decimals.and_then(|output| ERC20::decimalsCall::abi_decode_returns(&output, true).ok());
+        let total_supply = total_supply.and_then(|output| {
+            ERC20::totalSupplyCall::abi_decode_returns(&output, true).ok()
+        });
+
+        // a call to an address without code succeeds with an empty output
+        if symbol.is_none() && name.is_none() && decimals.is_none() && total_supply.is_none() {
+            return Err(anyhow::anyhow!("{} is not an ERC20 token", address));
+        }
+
+        Ok(Self {
+            address,
+            symbol: symbol.unwrap_or_default(),
+            name: name.unwrap_or_default(),
+            decimals: decimals.map(|d| d._0).unwrap_or_default(),
+            total_supply: total_supply.map(|t| t._0).unwrap_or_default(),
+        })
+    }
+
     pub async fn balance_of(
         &self,
         owner: Address,
@@ -217,15 +285,21 @@ impl ERC20Token {
 
 
 pub fn revert_msg(bytes: &Bytes) -> String {
-    if bytes.len() < 4 {
-        return "EVM Returned 0x (Empty Bytes)".to_string();
-    }
-    let error_data = &bytes[4..];
+    decode_revert(bytes).to_string()
+}
 
-    match String::from_utf8(error_data.to_vec()) {
-        Ok(s) => s.trim_matches(char::from(0)).to_string(),
-        Err(_) => "EVM Returned 0x (Empty Bytes)".to_string(),
+
+// `string` for most tokens, `bytes32` for older ones like MKR
+fn decode_string(output: &[u8]) -> Option<String> {
+    // decoded as bytes, a `string` with invalid UTF-8 is still shown
+    if let Ok(decoded) = <alloy::primitives::Bytes as SolValue>::abi_decode(output, true) {
+        return Some(String::from_utf8_lossy(&decoded).to_string());
+    }
+    if output.len() == 32 {
+        let bytes: Vec<u8> = output.iter().copied().take_while(|b| *b != 0).collect();
+        return Some(String::from_utf8_lossy(&bytes).to_string());
     }
+    None
 }
 
 
@@ -235,4 +309,45 @@ pub fn to_readable(amount: U256, token: ERC20Token) -> String {
     let amount_as_decimal = BigDecimal::from_str(&amount.to_string()).unwrap();
     let amount = amount_as_decimal / divisor;
     format!("{:.4} {}", amount, token.symbol)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abi_string(bytes: &[u8]) -> Vec<u8> {
+        let mut output = U256::from(32).to_be_bytes_vec();
+        output.extend_from_slice(&U256::from(bytes.len()).to_be_bytes::<32>());
+        output.extend_from_slice(bytes);
+        output.resize(64 + bytes.len().div_ceil(32) * 32, 0);
+        output
+    }
+
+    #[test]
+    fn decode_abi_string() {
+        assert_eq!(decode_string(&abi_string(b"USD Coin")), Some("USD Coin".to_string()));
+        assert_eq!(decode_string(&abi_string(b"")), Some(String::new()));
+    }
+
+    #[test]
+    fn decode_bytes32_string() {
+        // MKR returns its symbol as a bytes32 padded with zeros
+        let mut output = b"MKR".to_vec();
+        output.resize(32, 0);
+        assert_eq!(decode_string(&output), Some("MKR".to_string()));
+    }
+
+    #[test]
+    fn decode_invalid_utf8() {
+        let mut output = vec![b'M', 0xff, b'R'];
+        output.resize(32, 0);
+        assert_eq!(decode_string(&output), Some("M\u{fffd}R".to_string()));
+
+        assert_eq!(decode_string(&abi_string(&[b'M', 0xff, b'R'])), Some("M\u{fffd}R".to_string()));
+    }
+
+    #[test]
+    fn decode_invalid_output() {
+        assert_eq!(decode_string(&[]), None);
+        assert_eq!(decode_string(&[1, 2, 3]), None);
+    }
+}
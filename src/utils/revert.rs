@@ -0,0 +1,239 @@
+use alloy::dyn_abi::{ DynSolValue, JsonAbiExt };
+use alloy::hex;
+use alloy::json_abi::{ Error, JsonAbi };
+use alloy::primitives::{ Bytes, Selector, U256 };
+use alloy::sol_types::{ Panic, Revert, SolError };
+use hashbrown::HashMap;
+
+use std::fmt;
+
+use super::SignatureDb;
+use crate::inspectors::CallFrame;
+
+
+/// The reason of a revert, decoded from the revert data
+#[derive(Debug, Clone, PartialEq)]
+pub enum RevertReason {
+    /// `revert()` or `require` without a message, also the output of out of gas and other halts
+    Empty,
+
+    /// `revert("message")` or `require(condition, "message")`
+    Error(String),
+
+    /// A failed `assert`, an overflow, a division by zero... see [panic_reason]
+    Panic(U256),
+
+    /// A custom error found in the registered ABIs or in the [SignatureDb]
+    Custom {
+        error: Error,
+        args: Vec<DynSolValue>,
+    },
+
+    /// Revert data that could not be decoded, eg. a custom error of an unregistered ABI
+    Unknown(Bytes),
+}
+
+impl RevertReason {
+    /// The selector of the error, `None` for empty revert data
+    pub fn selector(&self) -> Option<Selector> {
+        match self {
+            RevertReason::Empty => None,
+            RevertReason::Error(_) => Some(Revert::SELECTOR.into()),
+            RevertReason::Panic(_) => Some(Panic::SELECTOR.into()),
+            RevertReason::Custom { error, .. } => Some(error.selector()),
+            RevertReason::Unknown(data) => data.get(..4).map(Selector::from_slice),
+        }
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Empty => write!(f, "EVM Returned 0x (Empty Bytes)"),
+            RevertReason::Error(reason) => write!(f, "{}", reason),
+            RevertReason::Panic(code) => write!(f, "Panic({:#x}): {}", code, panic_reason(*code)),
+            RevertReason::Custom { error, args } => {
+                let args: Vec<String> = args.iter().map(format_value).collect();
+                write!(f, "{}({})", error.name, args.join(", "))
+            }
+            RevertReason::Unknown(data) => write!(f, "Unknown revert data: 0x{}", hex::encode(data)),
+        }
+    }
+}
+
+/// Decodes revert data into a [RevertReason]
+///
+/// `Error(string)` and `Panic(uint256)` are always decoded, custom errors when their ABI is registered
+/// or when they are in the global [SignatureDb]
+#[derive(Debug, Clone, Default)]
+pub struct RevertDecoder {
+    // several errors can share a selector
+    errors: HashMap<Selector, Vec<Error>>,
+}
+
+impl RevertDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers all the custom errors of an ABI
+    pub fn register_abi(&mut self, abi: &JsonAbi) {
+        for error in abi.errors() {
+            self.register(error.clone());
+        }
+    }
+
+    /// Registers a custom error from its signature, eg. `error InsufficientBalance(uint256 available, uint256 required)`
+    pub fn register_error(&mut self, signature: &str) -> Result<(), anyhow::Error> {
+        let error = Error::parse(signature).map_err(|e| {
+            anyhow::anyhow!("Invalid error signature {}: {}", signature, e)
+        })?;
+        self.register(error);
+        Ok(())
+    }
+
+    pub fn decode(&self, data: &[u8]) -> RevertReason {
+        if data.is_empty() {
+            return RevertReason::Empty;
+        }
+        if let Ok(revert) = Revert::abi_decode(data, false) {
+            return RevertReason::Error(revert.reason);
+        }
+        if let Ok(panic) = Panic::abi_decode(data, false) {
+            return RevertReason::Panic(panic.code);
+        }
+
+        let Some(selector) = data.get(..4).map(Selector::from_slice) else {
+            return RevertReason::Unknown(Bytes::copy_from_slice(data));
+        };
+
+        // the registered ABIs take precedence over the signature database
+        let registered = self.errors.get(&selector).map(Vec::as_slice).unwrap_or_default();
+        let signatures = SignatureDb::global();
+        for error in registered.iter().chain(signatures.errors(selector)) {
+            if let Ok(args) = error.abi_decode_input(&data[4..], true) {
+                return RevertReason::Custom {
+                    error: error.clone(),
+                    args,
+                };
+            }
+        }
+        RevertReason::Unknown(Bytes::copy_from_slice(data))
+    }
+
+    /// Decodes the revert of the innermost failed call of a trace, see [CallFrame::innermost_revert]
+    ///
+    /// Returns `None` if the transaction did not fail
+    pub fn decode_trace(&self, frame: &CallFrame) -> Option<RevertReason> {
+        let frame = frame.innermost_revert()?;
+        let output = frame.output.clone().unwrap_or_default();
+        Some(self.decode(&output))
+    }
+
+    fn register(&mut self, error: Error) {
+        let errors = self.errors.entry(error.selector()).or_default();
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+}
+
+/// Decodes revert data with the custom errors of the global [SignatureDb], see [RevertDecoder] to register ABIs
+pub fn decode_revert(data: &[u8]) -> RevertReason {
+    RevertDecoder::new().decode(data)
+}
+
+/// The meaning of a Solidity panic code, with the same wording as geth's revert reasons
+pub fn panic_reason(code: U256) -> String {
+    let reason = match code.try_into().unwrap_or(u64::MAX) {
+        0x00 => "generic panic",
+        0x01 => "assert(false)",
+        0x11 => "arithmetic underflow or overflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "enum overflow",
+        0x22 => "invalid encoded storage byte array accessed",
+        0x31 => "out-of-bounds array access; popping on an empty array",
+        0x32 => "out-of-bounds access of an array or bytesN",
+        0x41 => "out of memory",
+        0x51 => "uninitialized function",
+        _ => {
+            return format!("unknown panic code: {:#x}", code);
+        }
+    };
+    reason.to_string()
+}
+
+fn format_value(value: &DynSolValue) -> String {
+    match value {
+        DynSolValue::Bool(b) => b.to_string(),
+        DynSolValue::Int(i, _) => i.to_string(),
+        DynSolValue::Uint(u, _) => u.to_string(),
+        DynSolValue::Address(address) => address.to_checksum(None),
+        DynSolValue::String(s) => format!("{:?}", s),
+        DynSolValue::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        DynSolValue::FixedBytes(word, size) => format!("0x{}", hex::encode(&word[..*size])),
+        DynSolValue::Function(function) => format!("0x{}", hex::encode(function)),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => {
+            let values: Vec<String> = values.iter().map(format_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+        DynSolValue::Tuple(values) => {
+            let values: Vec<String> = values.iter().map(format_value).collect();
+            format!("({})", values.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::bytes;
+
+    #[test]
+    fn error_string() {
+        let data = Revert::from("not enough balance").abi_encode();
+        let reason = decode_revert(&data);
+
+        assert_eq!(reason, RevertReason::Error("not enough balance".to_string()));
+        assert_eq!(reason.to_string(), "not enough balance");
+        assert_eq!(reason.selector(), Some(Revert::SELECTOR.into()));
+    }
+
+    #[test]
+    fn panic_code() {
+        let data = Panic::from(0x11).abi_encode();
+        let reason = decode_revert(&data);
+
+        assert_eq!(reason, RevertReason::Panic(U256::from(0x11)));
+        assert_eq!(reason.to_string(), "Panic(0x11): arithmetic underflow or overflow");
+        assert_eq!(panic_reason(U256::from(0x99)), "unknown panic code: 0x99");
+    }
+
+    #[test]
+    fn registered_custom_error() {
+        let signature = "error InsufficientBalance(uint256 available, uint256 required)";
+        let error = Error::parse(signature).unwrap();
+        let mut data = error.selector().to_vec();
+        data.extend_from_slice(&U256::from(1).to_be_bytes::<32>());
+        data.extend_from_slice(&U256::from(2).to_be_bytes::<32>());
+
+        // unknown until the error is registered
+        let mut decoder = RevertDecoder::new();
+        assert_eq!(decoder.decode(&data), RevertReason::Unknown(data.clone().into()));
+
+        decoder.register_error(signature).unwrap();
+        let reason = decoder.decode(&data);
+        assert_eq!(reason.to_string(), "InsufficientBalance(1, 2)");
+        assert_eq!(reason.selector(), Some(error.selector()));
+    }
+
+    #[test]
+    fn empty_and_short_data() {
+        assert_eq!(decode_revert(&[]), RevertReason::Empty);
+        assert_eq!(decode_revert(&[]).selector(), None);
+
+        let short = decode_revert(&bytes!("08c3"));
+        assert_eq!(short, RevertReason::Unknown(bytes!("08c3")));
+        assert_eq!(short.selector(), None);
+    }
+}
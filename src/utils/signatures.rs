@@ -0,0 +1,206 @@
+use alloy::hex;
+use alloy::json_abi::{ Error, Event, Function, JsonAbi };
+use alloy::primitives::{ Selector, B256 };
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+
+use std::path::Path;
+use std::sync::{ RwLock, RwLockReadGuard, RwLockWriteGuard };
+
+use crate::abi::parse_abi;
+
+
+// one `function`, `error` or `event` signature per line, `#` starts a comment
+const BUNDLED: &str = include_str!("signatures.txt");
+
+lazy_static! {
+    // used to label traces and decode reverts, users extend it with their own ABIs
+    static ref GLOBAL: RwLock<SignatureDb> = RwLock::new(SignatureDb::bundled());
+}
+
+/// An offline database of function selectors, error selectors and event topics
+///
+/// Several signatures can share a selector, the lookups return the first one that was added
+#[derive(Debug, Clone, Default)]
+pub struct SignatureDb {
+    functions: HashMap<Selector, Vec<Function>>,
+    errors: HashMap<Selector, Vec<Error>>,
+    events: HashMap<B256, Vec<Event>>,
+}
+
+impl SignatureDb {
+    /// An empty database, see [SignatureDb::bundled] for the common signatures
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The ERC20, ERC721, Multicall, Uniswap and OpenZeppelin signatures bundled with the crate
+    pub fn bundled() -> Self {
+        let mut db = Self::new();
+        let lines = BUNDLED.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            db.add_signature(line).expect("bundled signatures are valid");
+        }
+        db
+    }
+
+    /// The database used by [crate::utils::RevertDecoder] and the gas profiles, the bundled signatures by default
+    pub fn global() -> RwLockReadGuard<'static, SignatureDb> {
+        GLOBAL.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Extends the global database, eg. `SignatureDb::global_mut().add_abi_file("MyContract.json")`
+    pub fn global_mut() -> RwLockWriteGuard<'static, SignatureDb> {
+        GLOBAL.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a signature starting with `function`, `error` or `event`,
+    /// eg. `event Transfer(address indexed from, address indexed to, uint256 value)`
+    pub fn add_signature(&mut self, signature: &str) -> Result<(), anyhow::Error> {
+        let result = match signature.split_whitespace().next() {
+            Some("function") => Function::parse(signature).map(|function| self.add_function(function)),
+            Some("error") => Error::parse(signature).map(|error| self.add_error(error)),
+            Some("event") => Event::parse(signature).map(|event| self.add_event(event)),
+            _ => {
+                return Err(anyhow::anyhow!("Signature {} must start with function, error or event", signature));
+            }
+        };
+        result.map_err(|e| anyhow::anyhow!("Invalid signature {}: {}", signature, e))
+    }
+
+    /// Adds the functions, errors and events of an ABI
+    pub fn add_abi(&mut self, abi: &JsonAbi) {
+        for function in abi.functions() {
+            self.add_function(function.clone());
+        }
+        for error in abi.errors() {
+            self.add_error(error.clone());
+        }
+        for event in abi.events() {
+            self.add_event(event.clone());
+        }
+    }
+
+    /// Adds the ABI of a json file, either a plain ABI array or an artifact with an `abi` field
+    pub fn add_abi_file(&mut self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+        let json = std::fs::read_to_string(path.as_ref())?;
+        self.add_abi(&parse_abi(&json)?);
+        Ok(())
+    }
+
+    pub fn add_function(&mut self, function: Function) {
+        let functions = self.functions.entry(function.selector()).or_default();
+        if !functions.iter().any(|f| f.signature() == function.signature()) {
+            functions.push(function);
+        }
+    }
+
+    pub fn add_error(&mut self, error: Error) {
+        let errors = self.errors.entry(error.selector()).or_default();
+        if !errors.iter().any(|e| e.signature() == error.signature()) {
+            errors.push(error);
+        }
+    }
+
+    pub fn add_event(&mut self, event: Event) {
+        // ERC20 and ERC721 `Transfer` share a topic, only the indexed parameters differ
+        let indexed = |event: &Event| event.inputs.iter().map(|input| input.indexed).collect::<Vec<_>>();
+        let events = self.events.entry(event.selector()).or_default();
+        if !events.iter().any(|e| e.signature() == event.signature() && indexed(e) == indexed(&event)) {
+            events.push(event);
+        }
+    }
+
+    pub fn function(&self, selector: Selector) -> Option<&Function> {
+        self.functions(selector).first()
+    }
+
+    /// All the functions with this selector
+    pub fn functions(&self, selector: Selector) -> &[Function] {
+        self.functions.get(&selector).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn error(&self, selector: Selector) -> Option<&Error> {
+        self.errors(selector).first()
+    }
+
+    /// All the errors with this selector
+    pub fn errors(&self, selector: Selector) -> &[Error] {
+        self.errors.get(&selector).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Looks up an event by its topic 0
+    pub fn event(&self, topic: B256) -> Option<&Event> {
+        self.events(topic).first()
+    }
+
+    /// All the events with this topic 0
+    pub fn events(&self, topic: B256) -> &[Event] {
+        self.events.get(&topic).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Labels the calldata of a call with its function signature, eg. `transfer(address,uint256)`
+    ///
+    /// Unknown functions are labelled with their selector and calls without a selector with `fallback`
+    pub fn label_call(&self, input: &[u8]) -> String {
+        let Some(selector) = input.get(..4).map(Selector::from_slice) else {
+            return "fallback".to_string();
+        };
+        match self.function(selector) {
+            Some(function) => function.signature(),
+            None => format!("0x{}", hex::encode(selector)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{ bytes, keccak256 };
+
+    #[test]
+    fn bundled_signatures() {
+        let db = SignatureDb::bundled();
+
+        // transfer(0x11.., 1)
+        let input = bytes!(
+            "a9059cbb"
+            "0000000000000000000000001111111111111111111111111111111111111111"
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(db.label_call(&input), "transfer(address,uint256)");
+
+        let error = db.error(Selector::from_slice(&keccak256("ERC20InsufficientBalance(address,uint256,uint256)")[..4]));
+        assert_eq!(error.unwrap().name, "ERC20InsufficientBalance");
+
+        let event = db.event(keccak256("Transfer(address,address,uint256)")).unwrap();
+        assert_eq!(event.name, "Transfer");
+    }
+
+    #[test]
+    fn user_signatures() {
+        let mut db = SignatureDb::new();
+        let input = bytes!("d09de08a");
+        assert_eq!(db.label_call(&input), "0xd09de08a");
+
+        db.add_signature("function increment()").unwrap();
+        assert_eq!(db.label_call(&input), "increment()");
+
+        // adding the same signature twice keeps a single entry
+        db.add_signature("function increment()").unwrap();
+        assert_eq!(db.functions(Selector::from_slice(&input)).len(), 1);
+
+        assert!(db.add_signature("increment()").is_err());
+        assert!(db.add_signature("function increment(").is_err());
+    }
+
+    #[test]
+    fn unknown_selector() {
+        let db = SignatureDb::bundled();
+
+        assert_eq!(db.label_call(&bytes!("deadbeef")), "0xdeadbeef");
+        assert_eq!(db.label_call(&bytes!("dead")), "fallback");
+        assert_eq!(db.label_call(&[]), "fallback");
+        assert!(db.function(Selector::from_slice(&bytes!("deadbeef"))).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use alloy::primitives::Address;
+
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+
+use super::ERC20Token;
+use crate::forked_db::fork_db::ForkDB;
+
+
+/// A cache of ERC20 metadata, optionally persisted to a local json file
+///
+/// Tokens missing from the registry are resolved on the fork with [ERC20Token::from_fork]
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: BTreeMap<Address, ERC20Token>,
+
+    // the registry is saved to this file every time a token is resolved
+    path: Option<PathBuf>,
+}
+
+impl TokenRegistry {
+    /// A registry that is only kept in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the registry from a json file, the file is created on the first save if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref().to_path_buf();
+        let tokens: Vec<ERC20Token> = match path.exists() {
+            true => serde_json::from_str(&std::fs::read_to_string(&path)?)?,
+            false => Vec::new(),
+        };
+
+        Ok(Self {
+            tokens: tokens.into_iter().map(|token| (token.address, token)).collect(),
+            path: Some(path),
+        })
+    }
+
+    pub fn get(&self, address: Address) -> Option<&ERC20Token> {
+        self.tokens.get(&address)
+    }
+
+    /// Adds a token or replaces its metadata, call [TokenRegistry::save] to persist it
+    pub fn insert(&mut self, token: ERC20Token) {
+        self.tokens.insert(token.address, token);
+    }
+
+    /// All the tokens, sorted by address
+    pub fn tokens(&self) -> Vec<ERC20Token> {
+        self.tokens.values().cloned().collect()
+    }
+
+    /// Returns the metadata of a token, resolving it on the fork and saving the registry if it is new
+    pub fn resolve(&mut self, address: Address, fork_db: &mut ForkDB) -> Result<ERC20Token, anyhow::Error> {
+        if let Some(token) = self.tokens.get(&address) {
+            return Ok(token.clone());
+        }
+
+        let token = ERC20Token::from_fork(address, fork_db)?;
+        self.insert(token.clone());
+        self.save()?;
+        Ok(token)
+    }
+
+    /// Writes the registry to its file, does nothing for an in memory registry
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tokens: Vec<&ERC20Token> = self.tokens.values().collect();
+        std::fs::write(path, serde_json::to_string_pretty(&tokens)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{ address, hex, U256 };
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, Bytecode };
+
+    const MKR: Address = address!("9f8f72aa9304c8b593d555f12ef6589cc3a579a2");
+
+    // returns the bytes32 "MKR" to any call, like the MKR token for `name` and `symbol`
+    fn mkr_fork() -> ForkDB {
+        let mut code = vec![0x7f];
+        code.extend_from_slice(b"MKR");
+        code.resize(33, 0);
+        code.extend_from_slice(&hex!("60005260206000f3"));
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(MKR, AccountInfo {
+            code: Some(Bytecode::new_raw(code.into())),
+            ..Default::default()
+        });
+        ForkDB::offline(db)
+    }
+
+    #[test]
+    fn resolve_and_reopen() {
+        let path = std::env::temp_dir().join(format!("token-registry-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut registry = TokenRegistry::open(&path).unwrap();
+        assert!(registry.tokens().is_empty());
+
+        let token = registry.resolve(MKR, &mut mkr_fork()).unwrap();
+        assert_eq!(token.symbol, "MKR");
+        assert_eq!(token.name, "MKR");
+        // the bytes32 is not a valid uint8
+        assert_eq!(token.decimals, 0);
+
+        let reopened = TokenRegistry::open(&path).unwrap();
+        assert_eq!(reopened.get(MKR), Some(&token));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn resolve_returns_known_tokens() {
+        let token = ERC20Token {
+            address: MKR,
+            symbol: "MKR".to_string(),
+            name: "Maker".to_string(),
+            decimals: 18,
+            total_supply: U256::from(1000),
+        };
+        let mut registry = TokenRegistry::new();
+        registry.insert(token.clone());
+
+        // the fork has no code, the token is not resolved again
+        let mut fork_db = ForkDB::offline(CacheDB::new(EmptyDB::default()));
+        assert_eq!(registry.resolve(MKR, &mut fork_db).unwrap(), token);
+
+        let unknown = address!("1111111111111111111111111111111111111111");
+        assert!(registry.resolve(unknown, &mut fork_db).is_err());
+        assert_eq!(registry.tokens(), vec![token]);
+    }
+}
@@ -0,0 +1,172 @@
+use revm::primitives::{ EVMError, Env, InvalidTransaction, U256 };
+use revm::Evm;
+
+use crate::forked_db::{ fork_db::ForkDB, database_error::DatabaseError };
+
+
+/// The reason a transaction would not be accepted on-chain
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TxRejection {
+    #[error("nonce too low: tx nonce {tx}, account nonce {state}")]
+    NonceTooLow { tx: u64, state: u64 },
+
+    #[error("nonce too high: tx nonce {tx}, account nonce {state}")]
+    NonceTooHigh { tx: u64, state: u64 },
+
+    /// The sender cannot pay for `gas * max_fee + value` (plus the max blob fee)
+    #[error("insufficient funds for gas * max fee + value: have {balance} want {cost}")]
+    InsufficientFunds { cost: U256, balance: U256 },
+
+    #[error("max fee per gas {max_fee} less than block base fee {base_fee}")]
+    FeeCapTooLow { max_fee: U256, base_fee: U256 },
+
+    #[error("max priority fee per gas higher than max fee per gas")]
+    TipAboveFeeCap,
+
+    #[error("gas limit {gas_limit} exceeds block gas limit {block_gas_limit}")]
+    GasLimitExceedsBlock { gas_limit: u64, block_gas_limit: U256 },
+
+    #[error("intrinsic gas too low: gas limit {gas_limit}")]
+    IntrinsicGasTooLow { gas_limit: u64 },
+
+    /// EIP-3607: the sender has deployed code
+    #[error("sender not an eoa")]
+    SenderNotEoa,
+
+    #[error("invalid chain id")]
+    InvalidChainId,
+
+    /// Any other validation failure reported by revm
+    #[error("{0}")]
+    Other(InvalidTransaction),
+}
+
+impl TxRejection {
+    /// Converts a revm [InvalidTransaction] into a [TxRejection], using the [Env] to fill in the details
+    pub fn from_invalid(err: InvalidTransaction, env: &Env) -> Self {
+        match err {
+            InvalidTransaction::NonceTooLow { tx, state } => TxRejection::NonceTooLow { tx, state },
+            InvalidTransaction::NonceTooHigh { tx, state } => TxRejection::NonceTooHigh { tx, state },
+            InvalidTransaction::LackOfFundForMaxFee { fee, balance } => {
+                TxRejection::InsufficientFunds { cost: *fee, balance: *balance }
+            }
+            InvalidTransaction::GasPriceLessThanBasefee => {
+                TxRejection::FeeCapTooLow { max_fee: env.tx.gas_price, base_fee: env.block.basefee }
+            }
+            InvalidTransaction::PriorityFeeGreaterThanMaxFee => TxRejection::TipAboveFeeCap,
+            InvalidTransaction::CallerGasLimitMoreThanBlock => {
+                TxRejection::GasLimitExceedsBlock {
+                    gas_limit: env.tx.gas_limit,
+                    block_gas_limit: env.block.gas_limit,
+                }
+            }
+            InvalidTransaction::CallGasCostMoreThanGasLimit => {
+                TxRejection::IntrinsicGasTooLow { gas_limit: env.tx.gas_limit }
+            }
+            InvalidTransaction::RejectCallerWithCode => TxRejection::SenderNotEoa,
+            InvalidTransaction::InvalidChainId => TxRejection::InvalidChainId,
+            other => TxRejection::Other(other),
+        }
+    }
+
+    /// Extracts the [TxRejection] from an [EVMError] if the error is a transaction validation failure
+    pub fn from_evm_error(err: &EVMError<DatabaseError>, env: &Env) -> Option<Self> {
+        match err {
+            EVMError::Transaction(invalid) => Some(Self::from_invalid(invalid.clone(), env)),
+            _ => None,
+        }
+    }
+}
+
+/// Checks whether the transaction currently set in the [Evm] would be valid on-chain without executing it
+///
+/// The [Evm] should be created with [crate::EvmMode::Strict] and `tx.nonce` should be set,
+/// otherwise the relaxed checks are skipped
+///
+/// Returns `Ok(Err(TxRejection))` if the transaction is invalid
+pub fn validate_tx<EXT>(
+    evm: &mut Evm<'_, EXT, ForkDB>
+) -> Result<Result<(), TxRejection>, anyhow::Error> {
+    match evm.preverify_transaction() {
+        Ok(_) => Ok(Ok(())),
+        Err(EVMError::Transaction(invalid)) => {
+            Ok(Err(TxRejection::from_invalid(invalid, &evm.context.evm.env)))
+        }
+        Err(e) => Err(anyhow::anyhow!("Failed to validate transaction: {:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ new_evm_with_mode, EvmMode };
+    use alloy::primitives::{ address, Address };
+    use alloy::rpc::types::eth::Block;
+    use revm::db::{ CacheDB, EmptyDB };
+    use revm::primitives::{ AccountInfo, TransactTo, TxEnv };
+
+    const CALLER: Address = address!("1111111111111111111111111111111111111111");
+    const GWEI: u64 = 1_000_000_000;
+
+    fn validate(balance: U256, tx: TxEnv) -> Result<(), TxRejection> {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(CALLER, AccountInfo { balance, nonce: 5, ..Default::default() });
+
+        let mut block = Block::default();
+        block.header.number = Some(1);
+        block.header.gas_limit = 30_000_000;
+        block.header.base_fee_per_gas = Some(GWEI as u128);
+        let mut evm = new_evm_with_mode(ForkDB::offline(db), block, EvmMode::Strict);
+
+        *evm.tx_mut() = TxEnv {
+            caller: CALLER,
+            transact_to: TransactTo::Call(address!("2222222222222222222222222222222222222222")),
+            ..tx
+        };
+        validate_tx(&mut evm).unwrap()
+    }
+
+    fn tx(nonce: u64, gas_limit: u64) -> TxEnv {
+        TxEnv {
+            gas_limit,
+            gas_price: U256::from(GWEI),
+            nonce: Some(nonce),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(validate(U256::from(21_000 * GWEI), tx(5, 21_000)), Ok(()));
+    }
+
+    #[test]
+    fn nonce_too_low() {
+        assert_eq!(
+            validate(U256::from(21_000 * GWEI), tx(3, 21_000)),
+            Err(TxRejection::NonceTooLow { tx: 3, state: 5 })
+        );
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        assert_eq!(
+            validate(U256::from(21_000 * GWEI - 1), tx(5, 21_000)),
+            Err(TxRejection::InsufficientFunds {
+                cost: U256::from(21_000 * GWEI),
+                balance: U256::from(21_000 * GWEI - 1),
+            })
+        );
+    }
+
+    #[test]
+    fn gas_limit_above_block() {
+        assert_eq!(
+            validate(U256::MAX, tx(5, 40_000_000)),
+            Err(TxRejection::GasLimitExceedsBlock {
+                gas_limit: 40_000_000,
+                block_gas_limit: U256::from(30_000_000),
+            })
+        );
+    }
+}